@@ -0,0 +1,258 @@
+//! Procedural macros for writing reaction networks directly in rust source.
+//!
+//! The body of [crn!] uses the same syntax as a csv network with rows ending in semicolons instead of new lines.
+//! Species counts are written `A = 5;` and parameters are declared with `let`.
+//! The network is parsed by [MARlea_parser] at compile time so any error is reported as a compiler error
+//! pointing at the offending part of the macro body.
+//!
+//! ```ignore
+//! use marlea_parser_macros::crn;
+//!
+//! let reaction_network = crn! {
+//!     let fast = 10000;
+//!     A + 2 B => C, fast;
+//!     C <=> D, 1, 2;
+//!     setup.call => NULL, fast / 10;
+//!     A = 5;
+//! };
+//! ```
+//!
+//! The expanded code builds a `ReactionNetwork` through `::marlea_engine`, which must be a dependency of the calling crate.
+
+use std::ops::Range;
+
+use proc_macro2::{Delimiter, Spacing, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, reaction::{Reaction, term::Term}};
+use MARlea_parser::{MarleaParser, MarleaParserError};
+
+/// Builds a `ReactionNetwork` from reactions, species counts and parameters written in csv syntax
+#[proc_macro]
+pub fn crn(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(input.into()).into()
+}
+
+/// csv source rendered from a macro body along with the span of the token each piece of text came from
+#[derive(Default)]
+struct Rendered {
+    source: String,
+    spans: Vec<(Range<usize>, Span)>,
+    // the next token is written directly after the previous one without a space
+    joined: bool,
+}
+
+impl Rendered {
+    fn push(&mut self, text: &str, span: Span) {
+        if !self.joined && !self.source.is_empty() && !self.source.ends_with('\n') {
+            self.source.push(' ');
+        }
+        let start = self.source.len();
+        self.source.push_str(text);
+        self.spans.push((start..self.source.len(), span));
+        self.joined = false;
+    }
+
+    /// span of the token which produced the text at offset, or of the last token when offset is past the end of a row
+    fn span_at(&self, offset: usize) -> Span {
+        self.spans.iter()
+        .find(|(range, _)| offset < range.end)
+        .or(self.spans.last())
+        .map_or(Span::call_site(), |(_, span)| *span)
+    }
+}
+
+fn expand(input: TokenStream) -> TokenStream {
+    let rendered = render(input);
+
+    match MarleaParser::parse_str(&rendered.source) {
+        Ok(reaction_network) => build(&reaction_network),
+        Err(err) => compile_error(&rendered, &err),
+    }
+}
+
+/// render the macro body as csv, one row per statement
+fn render(input: TokenStream) -> Rendered {
+    let mut rendered = Rendered::default();
+    let mut statement = Vec::new();
+
+    for token in input {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ';' => {
+                render_statement(std::mem::take(&mut statement), &mut rendered);
+                rendered.source.push('\n');
+            },
+            _ => statement.push(token),
+        }
+    }
+    // the final statement does not need a semicolon
+    if !statement.is_empty() {
+        render_statement(statement, &mut rendered);
+        rendered.source.push('\n');
+    }
+
+    rendered
+}
+
+fn render_statement(statement: Vec<TokenTree>, rendered: &mut Rendered) {
+    let mut tokens = statement.into_iter().peekable();
+    let mut previous_arrow = false;
+
+    // `let name = value` declares a parameter, any other `name = value` sets a species count
+    let is_parameter = matches!(tokens.peek(), Some(TokenTree::Ident(ident)) if ident == "let");
+    if is_parameter {
+        if let Some(token) = tokens.next() {
+            rendered.push("#param", token.span());
+        }
+    }
+
+    while let Some(token) = tokens.next() {
+        let arrow = matches!(&token, TokenTree::Punct(punct) if is_arrow(punct.as_char()));
+        match &token {
+            // rust splits <=> into <= and > so only an = standing on its own separates a species from its count
+            TokenTree::Punct(punct) if punct.as_char() == '=' && !previous_arrow && !is_parameter
+                && !matches!(tokens.peek(), Some(TokenTree::Punct(next)) if is_arrow(next.as_char())) => {
+                rendered.push(",", punct.span());
+            },
+            _ => {
+                rendered.joined |= arrow && previous_arrow;
+                render_token(token, rendered);
+            },
+        }
+        previous_arrow = arrow;
+    }
+}
+
+fn is_arrow(character: char) -> bool {
+    matches!(character, '<' | '=' | '>')
+}
+
+fn render_token(token: TokenTree, rendered: &mut Rendered) {
+    match token {
+        TokenTree::Group(group) => {
+            let (open, close) = match group.delimiter() {
+                Delimiter::Parenthesis => ("(", ")"),
+                Delimiter::Brace => ("{", "}"),
+                Delimiter::Bracket => ("[", "]"),
+                Delimiter::None => ("", ""),
+            };
+            rendered.push(open, group.span_open());
+            rendered.joined = true;
+            for inner in group.stream() {
+                render_token(inner, rendered);
+            }
+            rendered.joined = true;
+            rendered.push(close, group.span_close());
+        },
+        TokenTree::Punct(punct) => {
+            // dots join the segments of species names such as setup.call
+            if punct.as_char() == '.' {
+                rendered.joined = true;
+            }
+            rendered.push(&punct.to_string(), punct.span());
+            rendered.joined = punct.as_char() == '.' || punct.spacing() == Spacing::Joint;
+        },
+        TokenTree::Ident(ident) => rendered.push(&ident.to_string(), ident.span()),
+        TokenTree::Literal(literal) => rendered.push(&literal.to_string(), literal.span()),
+    }
+}
+
+/// report a parser error as a compiler error at the token it points to
+fn compile_error(rendered: &Rendered, error: &MarleaParserError) -> TokenStream {
+    let span = match error.location() {
+        Some(location) => rendered.span_at(location.start),
+        None => Span::call_site(),
+    };
+    let label = error.label();
+    let mut message = match label.is_empty() {
+        true => error.summary(),
+        false => format!("{}: {}", error.summary(), label),
+    };
+    if let Some(help) = error.help() {
+        message.push_str(&format!("\n\nhelp: {}", help));
+    }
+
+    quote_spanned! {span=> ::core::compile_error!(#message) }
+}
+
+/// code constructing the parsed network, sorted so the same body always expands to the same code
+fn build(reaction_network: &ReactionNetwork) -> TokenStream {
+    let mut reactions = reaction_network.get_reactions().iter().collect::<Vec<&Reaction>>();
+    reactions.sort_by_cached_key(|reaction| (term_key(reaction.get_reactants()), term_key(reaction.get_products()), reaction.get_reaction_rate()));
+    let reactions = reactions.into_iter().map(|reaction| {
+        let reactants = reaction.get_reactants().iter().map(build_term);
+        let products = reaction.get_products().iter().map(build_term);
+        let reaction_rate = reaction.get_reaction_rate();
+        quote! { reactions.insert(Reaction::new(::std::vec![#(#reactants),*], ::std::vec![#(#products),*], #reaction_rate)); }
+    });
+
+    let mut species_counts = reaction_network.get_solution().species_counts.iter()
+        .map(|(name, count)| (name.0.as_str(), count.0))
+        .collect::<Vec<(&str, u64)>>();
+    species_counts.sort_unstable();
+    let species_counts = species_counts.into_iter().map(|(name, count)| {
+        quote! { species_counts.insert(Name(::std::string::String::from(#name)), Count(#count)); }
+    });
+
+    quote! {{
+        use ::marlea_engine::trial::reaction_network::{ReactionNetwork, solution::{Name, Count, Solution}, reaction::{Reaction, term::Term}};
+        let mut reactions = ::std::collections::HashSet::new();
+        #(#reactions)*
+        let mut species_counts = ::std::collections::HashMap::new();
+        #(#species_counts)*
+        ReactionNetwork::new(reactions, Solution { species_counts })
+    }}
+}
+
+fn build_term(term: &Term) -> TokenStream {
+    let name = term.get_species_name().0.as_str();
+    let coefficient = term.get_coefficient().0;
+    quote! { Term::new(Name(::std::string::String::from(#name)), Count(#coefficient)) }
+}
+
+fn term_key(terms: &[Term]) -> Vec<(String, u64)> {
+    terms.iter().map(|term| (term.get_species_name().0.clone(), term.get_coefficient().0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::TokenStream;
+    use quote::quote;
+
+    use MARlea_parser::MarleaParser;
+
+    use crate::{expand, render};
+
+    #[test]
+    fn renders_statements_as_csv_rows() {
+        let rendered = render(quote! {
+            let fast = 1e4;
+            A + 2 B => C, fast * 10;
+            C <=> D, 1, 2;
+            setup.call + X.not.index.0 => NULL, (n + 1) / 2;
+            A = 5
+        });
+        assert_eq!(rendered.source, concat!(
+            "#param fast = 1e4\n",
+            "A + 2 B => C , fast * 10\n",
+            "C <=> D , 1 , 2\n",
+            "setup.call + X.not.index.0 => NULL , (n + 1) / 2\n",
+            "A , 5\n",
+        ));
+    }
+
+    #[test]
+    fn reports_errors_at_the_offending_token() {
+        let body: TokenStream = "A + B => C, 10;\nA => B, slow;".parse().unwrap();
+        let expanded = expand(body).to_string();
+        assert!(expanded.contains("compile_error"));
+        assert!(expanded.contains("undefined parameter `slow`"));
+
+        // the error is spanned to the undefined parameter rather than the whole macro
+        let rendered = render("A => B, slow;".parse().unwrap());
+        let error = MarleaParser::parse_str(&rendered.source).unwrap_err();
+        let start = error.location().unwrap().start;
+        let (range, _) = rendered.spans.iter().find(|(range, _)| range.contains(&start)).unwrap();
+        assert_eq!(&rendered.source[range.clone()], "slow");
+    }
+}
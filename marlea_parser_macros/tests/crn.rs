@@ -0,0 +1,25 @@
+use marlea_parser_macros::crn;
+use MARlea_parser::MarleaParser;
+
+#[test]
+fn crn_matches_parsed_csv() {
+    let from_macro = crn! {
+        let fast = 1e3;
+        A + 2 B => C, fast;
+        C <=> D, 1, fast / 10;
+        setup.call => NULL, 5;
+        A = 5;
+        B = 2 * 3;
+    };
+    let from_csv = MarleaParser::parse_str(concat!(
+        "#param fast = 1e3\n",
+        "A + 2 B => C,fast,\n",
+        "C <=> D,1,fast / 10,\n",
+        "setup.call => NULL,5,\n",
+        "A,5,\n",
+        "B,6,\n",
+    )).unwrap();
+
+    assert_eq!(from_macro.get_reactions(), from_csv.get_reactions());
+    assert_eq!(from_macro.get_solution().species_counts, from_csv.get_solution().species_counts);
+}
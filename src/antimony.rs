@@ -0,0 +1,516 @@
+//! Reads models written in the Antimony language into a reaction network.
+//!
+//! Species are declared with `species` or by appearing in a reaction, and are given initial counts by assignments such as `A = 5`.
+//! Any other assigned name is a rate constant which may be used by rate laws and other assignments.
+//! Every reaction must have a mass action rate law such as `J1: A + B -> C; k1*A*B`,
+//! reversible reactions with a law of the form `kf*A*B - kr*C` become a forward and a reverse reaction.
+//! A compartment in a rate law multiplies the rate by its size, as it does for SBML.
+//!
+//! ```text
+//! model toggle()
+//!   species A, B
+//!   J1: A + 2 B => C; k1*A*B^2
+//!   J2: C -> D; kf*C - kr*D
+//!   A = 5; B = 10
+//!   k1 = 10; kf = 1; kr = k1 / 5
+//! end
+//! ```
+//!
+//! Rules, events, functions and boundary species have no equivalent in a reaction network and are reported as diagnostics.
+
+use std::{collections::{HashMap, HashSet}, sync::OnceLock};
+
+use pest::{Parser, iterators::Pair, pratt_parser::{Assoc, Op, PrattParser}};
+use pest_derive::Parser;
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, solution::{Name, Count, Solution}, reaction::{Reaction, term::Term}};
+
+use crate::{MarleaParserError, SourceSpan, mass_action::MassAction, number::{ArithmeticError, IntegerConversionError, Rational}};
+
+#[derive(Parser)]
+#[grammar = "grammars/antimony.pest"]
+pub(crate) struct AntimonyParser;
+
+// operator precedence for expression tokens, lowest first
+static EXPRESSION_PARSER: OnceLock<PrattParser<Rule>> = OnceLock::new();
+
+/// an assignment value or rate law
+enum Expression {
+    Number(Rational),
+    Identifier(String),
+    // function calls are parsed so they can be reported, but never evaluated
+    Call,
+    Negate(Box<Located>),
+    Binary(Rule, Box<Located>, Box<Located>),
+}
+
+/// an expression and the source text it was parsed from
+struct Located {
+    expression: Expression,
+    location: SourceSpan,
+}
+
+/// a species and its stoichiometry on one side of a reaction
+struct Participant {
+    name: String,
+    coefficient: Count,
+}
+
+/// a reaction statement before its rate law has been interpreted
+struct ReactionStatement {
+    name: String,
+    reactants: Vec<Participant>,
+    products: Vec<Participant>,
+    reversible: bool,
+    rate_law: Located,
+}
+
+/// everything declared by a model, antimony statements may appear in any order
+#[derive(Default)]
+struct Model {
+    species: Vec<String>,
+    compartments: HashSet<String>,
+    // the last assignment to a name is its value
+    assignments: HashMap<String, Located>,
+    reactions: Vec<ReactionStatement>,
+}
+
+impl Model {
+    fn declare_species(&mut self, name: &str) {
+        if !self.species.iter().any(|species| species == name) {
+            self.species.push(name.to_string());
+        }
+    }
+}
+
+impl AntimonyParser {
+    /// parse an antimony model into a reaction network, failing on the first unsupported statement
+    pub(crate) fn as_reaction_network(source: &str) -> Result<ReactionNetwork, MarleaParserError> {
+        let (reaction_network, mut diagnostics) = Self::as_reaction_network_recovering(source);
+        match diagnostics.is_empty() {
+            true => Result::Ok(reaction_network),
+            false => Result::Err(diagnostics.remove(0)),
+        }
+    }
+
+    /// parse an antimony model into a reaction network, skipping any statement which can not be imported.
+    /// Returns the network built from every supported species and reaction along with a diagnostic for everything else.
+    pub(crate) fn as_reaction_network_recovering(source: &str) -> (ReactionNetwork, Vec<MarleaParserError>) {
+        let mut reactions = HashSet::new();
+        let mut species_counts = HashMap::new();
+        let mut diagnostics = Vec::new();
+
+        match Self::parse(Rule::model, source) {
+            Ok(mut token_stream) => {
+                let mut model = Model::default();
+                if let Some(token) = token_stream.next() {
+                    for statement in token.into_inner() {
+                        if let Err(msg) = Self::interpret_statement(statement, &mut model) {
+                            diagnostics.push(msg);
+                        }
+                    }
+                }
+                Self::interpret_model(&model, &mut reactions, &mut species_counts, &mut diagnostics);
+            },
+            Err(err) => diagnostics.push(MarleaParserError::Antimony { location: SourceSpan::from_pest_error(&err), source: Box::new(err) }),
+        }
+
+        (ReactionNetwork::new(reactions, Solution { species_counts }), diagnostics)
+    }
+
+    /// record a single statement in the model
+    fn interpret_statement(token: Pair<'_, Rule>, model: &mut Model) -> Result<(), MarleaParserError> {
+        let location = SourceSpan::from_pair(&token);
+        match token.as_rule() {
+            Rule::reaction => {
+                let index = model.reactions.len();
+                let reaction = Self::as_reaction(token, index)?;
+                for participant in reaction.reactants.iter().chain(&reaction.products) {
+                    model.declare_species(&participant.name);
+                }
+                model.reactions.push(reaction);
+            },
+            Rule::species_declaration => {
+                for declared in token.into_inner().filter(|token| token.as_rule() == Rule::declared_species) {
+                    let mut name = None;
+                    for sub_token in declared.into_inner() {
+                        match sub_token.as_rule() {
+                            Rule::boundary => return Result::Err(MarleaParserError::UnsupportedAntimony { location: SourceSpan::from_pair(&sub_token), construct: "boundary species" }),
+                            Rule::identifier if name.is_none() => {
+                                model.declare_species(sub_token.as_str());
+                                name = Some(sub_token.as_str().to_string());
+                            },
+                            Rule::expression => {
+                                if let Some(name) = name.clone() {
+                                    model.assignments.insert(name, Self::as_expression(sub_token)?);
+                                }
+                            },
+                            // the compartment a species is in
+                            _ => (),
+                        }
+                    }
+                }
+            },
+            Rule::compartment_declaration => {
+                for declared in token.into_inner().filter(|token| token.as_rule() == Rule::declared_compartment) {
+                    let mut inner = declared.into_inner();
+                    if let Some(identifier) = inner.next() {
+                        model.compartments.insert(identifier.as_str().to_string());
+                        // the size, which may also be given by a later assignment
+                        if let Some(size) = inner.next() {
+                            model.assignments.insert(identifier.as_str().to_string(), Self::as_expression(size)?);
+                        }
+                    }
+                }
+            },
+            Rule::assignment => {
+                let mut inner = token.into_inner().filter(|token| token.as_rule() != Rule::modifier_keyword);
+                match (inner.next(), inner.next()) {
+                    (Some(name), Some(value)) => {
+                        model.assignments.insert(name.as_str().to_string(), Self::as_expression(value)?);
+                    },
+                    _ => return Result::Err(MarleaParserError::InvalidAntimony { location, reason: String::from("assignment is missing a name or value") }),
+                }
+            },
+            Rule::assignment_rule => return Result::Err(MarleaParserError::UnsupportedAntimony { location, construct: "assignment rule" }),
+            Rule::rate_rule => return Result::Err(MarleaParserError::UnsupportedAntimony { location, construct: "rate rule" }),
+            Rule::event => return Result::Err(MarleaParserError::UnsupportedAntimony { location, construct: "event" }),
+            Rule::function_definition => return Result::Err(MarleaParserError::UnsupportedAntimony { location, construct: "function definition" }),
+            // model header and end keywords
+            _ => (),
+        }
+        Result::Ok(())
+    }
+
+    /// resolve initial counts and rate laws once every statement has been seen
+    fn interpret_model(model: &Model, reactions: &mut HashSet<Reaction>, species_counts: &mut HashMap<Name, Count>, diagnostics: &mut Vec<MarleaParserError>) {
+        for species in &model.species {
+            let count = match model.assignments.get(species) {
+                Some(value) => Self::evaluate(value, model, &mut Vec::new()).and_then(|count| match count.to_u64() {
+                    Ok(count) => Result::Ok(Count(count)),
+                    Err(IntegerConversionError::NotIntegral) => Result::Err(MarleaParserError::NonIntegral { location: value.location.clone(), text: count.to_string() }),
+                    Err(_) => Result::Err(MarleaParserError::BadSpeciesCount { location: value.location.clone(), text: count.to_string() }),
+                }),
+                None => Result::Ok(Count(0)),
+            };
+            match count {
+                Ok(count) => {
+                    species_counts.insert(Name(species.clone()), count);
+                },
+                Err(msg) => {
+                    // the species still exists for the reactions which use it
+                    species_counts.insert(Name(species.clone()), Count(0));
+                    diagnostics.push(msg);
+                },
+            }
+        }
+
+        for reaction in &model.reactions {
+            match Self::as_reactions(reaction, model) {
+                Ok(interpreted) => reactions.extend(interpreted),
+                Err(msg) => diagnostics.push(msg),
+            }
+        }
+    }
+
+    fn as_reaction(token: Pair<'_, Rule>, index: usize) -> Result<ReactionStatement, MarleaParserError> {
+        let location = SourceSpan::from_pair(&token);
+        // unlabelled reactions are named the way antimony names them
+        let mut name = format!("_J{}", index);
+        let mut reactants = Vec::new();
+        let mut products = Vec::new();
+        let mut reversible = false;
+        let mut rate_law = None;
+
+        for sub_token in token.into_inner() {
+            match sub_token.as_rule() {
+                Rule::label => name = sub_token.into_inner().as_str().to_string(),
+                Rule::reactants => reactants = Self::as_participants(sub_token)?,
+                Rule::products => products = Self::as_participants(sub_token)?,
+                Rule::reversible => reversible = true,
+                Rule::rate_law => rate_law = sub_token.into_inner().next().map(Self::as_expression).transpose().map_err(MarleaParserError::into_rate_error)?,
+                _ => (),
+            }
+        }
+
+        match rate_law {
+            Some(rate_law) => Result::Ok(ReactionStatement { name, reactants, products, reversible, rate_law }),
+            None => Result::Err(MarleaParserError::NotMassAction { location, reaction: name }),
+        }
+    }
+
+    fn as_participants(token: Pair<'_, Rule>) -> Result<Vec<Participant>, MarleaParserError> {
+        let mut participants = Vec::new();
+        for term in token.into_inner().flat_map(Pair::into_inner) {
+            let location = SourceSpan::from_pair(&term);
+            let mut coefficient = Count(1);
+            let mut name = String::new();
+            for sub_token in term.into_inner() {
+                match sub_token.as_rule() {
+                    Rule::stoichiometry => {
+                        let text = sub_token.as_str();
+                        coefficient = match Rational::from_decimal(text).map(Rational::to_u64) {
+                            Ok(Err(IntegerConversionError::NotIntegral)) | Err(ArithmeticError::Underflow) => return Result::Err(MarleaParserError::NonIntegral { location, text: text.to_string() }),
+                            Ok(Ok(0)) | Ok(Err(IntegerConversionError::Negative | IntegerConversionError::Overflow)) | Err(_) => {
+                                return Result::Err(MarleaParserError::BadCoefficient { location, text: text.to_string() })
+                            },
+                            Ok(Ok(coefficient)) => Count(coefficient),
+                        };
+                    },
+                    Rule::boundary => return Result::Err(MarleaParserError::UnsupportedAntimony { location, construct: "boundary species" }),
+                    Rule::identifier => name = sub_token.as_str().to_string(),
+                    _ => (),
+                }
+            }
+            participants.push(Participant { name, coefficient });
+        }
+        Result::Ok(participants)
+    }
+
+    /// build an expression tree from an expression token
+    fn as_expression(token: Pair<'_, Rule>) -> Result<Located, MarleaParserError> {
+        EXPRESSION_PARSER.get_or_init(|| {
+            PrattParser::new()
+            .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::subtract, Assoc::Left))
+            .op(Op::infix(Rule::multiply, Assoc::Left) | Op::infix(Rule::divide, Assoc::Left))
+            .op(Op::prefix(Rule::negate))
+            .op(Op::infix(Rule::power, Assoc::Right))
+        })
+        .map_primary(|operand| {
+            let location = SourceSpan::from_pair(&operand);
+            let expression = match operand.as_rule() {
+                Rule::number => match Rational::from_decimal(operand.as_str()) {
+                    Ok(value) => Expression::Number(value),
+                    Err(ArithmeticError::Underflow) => return Result::Err(MarleaParserError::NonIntegral { location, text: operand.as_str().to_string() }),
+                    Err(err) => return Result::Err(Self::arithmetic_error(err, location)),
+                },
+                Rule::identifier => Expression::Identifier(operand.as_str().to_string()),
+                Rule::call => Expression::Call,
+                Rule::expression => return Self::as_expression(operand),
+                _ => return Result::Err(MarleaParserError::InvalidAntimony { location, reason: String::from("expected a number or identifier") }),
+            };
+            Result::Ok(Located { expression, location })
+        })
+        .map_prefix(|operator, operand| {
+            let operand = operand?;
+            let location = Self::join(SourceSpan::from_pair(&operator), &operand.location);
+            Result::Ok(Located { expression: Expression::Negate(Box::new(operand)), location })
+        })
+        .map_infix(|left, operator, right| {
+            let (left, right) = (left?, right?);
+            let location = Self::join(left.location.clone(), &right.location);
+            Result::Ok(Located { expression: Expression::Binary(operator.as_rule(), Box::new(left), Box::new(right)), location })
+        })
+        .parse(token.into_inner())
+    }
+
+    /// location covering from the start of one span to the end of another
+    fn join(mut start: SourceSpan, end: &SourceSpan) -> SourceSpan {
+        start.end = end.end;
+        start
+    }
+
+    /// the value of an expression, names are looked up through their assignments.
+    /// resolving holds the names currently being evaluated so circular definitions are caught
+    fn evaluate(value: &Located, model: &Model, resolving: &mut Vec<String>) -> Result<Rational, MarleaParserError> {
+        let location = value.location.clone();
+        let result = match &value.expression {
+            Expression::Number(number) => Result::Ok(*number),
+            Expression::Identifier(name) => {
+                if resolving.contains(name) {
+                    return Result::Err(MarleaParserError::InvalidAntimony { location, reason: format!("`{}` is defined in terms of itself", name) });
+                }
+                match model.assignments.get(name) {
+                    Some(assigned) => {
+                        resolving.push(name.clone());
+                        let assigned = Self::evaluate(assigned, model, resolving);
+                        resolving.pop();
+                        return assigned;
+                    },
+                    None if model.species.contains(name) => Result::Ok(Rational::from_integer(0)),
+                    None => return Result::Err(MarleaParserError::InvalidAntimony { location, reason: format!("`{}` is never assigned a value", name) }),
+                }
+            },
+            Expression::Call => return Result::Err(MarleaParserError::UnsupportedAntimony { location, construct: "function call" }),
+            Expression::Negate(operand) => Self::evaluate(operand, model, resolving)?.negate(),
+            Expression::Binary(operator, left, right) => {
+                let (left, right) = (Self::evaluate(left, model, resolving)?, Self::evaluate(right, model, resolving)?);
+                match operator {
+                    Rule::add => left.add(right),
+                    Rule::subtract => left.subtract(right),
+                    Rule::multiply => left.multiply(right),
+                    Rule::divide => left.divide(right),
+                    _ => left.power(right),
+                }
+            },
+        };
+        result.map_err(|err| Self::arithmetic_error(err, location))
+    }
+
+    /// a reaction statement as a single reaction, or a forward and reverse reaction for reversible mass action laws
+    fn as_reactions(reaction: &ReactionStatement, model: &Model) -> Result<Vec<Reaction>, MarleaParserError> {
+        let as_terms = |participants: &[Participant]| -> Vec<Term> {
+            participants.iter().map(|participant| Term::new(Name(participant.name.clone()), participant.coefficient.clone())).collect()
+        };
+        let reactants = as_terms(&reaction.reactants);
+        let products = as_terms(&reaction.products);
+        let rate_law = &reaction.rate_law;
+
+        if let Some(rate) = Self::mass_action_rate(rate_law, &reactants, model)? {
+            return Result::Ok(vec![Reaction::new(reactants, products, Self::as_rate(rate_law, rate)?)]);
+        }
+
+        // a reversible law is the difference of a forward and a reverse mass action term
+        if let (true, Expression::Binary(Rule::subtract, forward, reverse)) = (reaction.reversible, &rate_law.expression) {
+            let forward_rate = Self::mass_action_rate(forward, &reactants, model)?;
+            let reverse_rate = Self::mass_action_rate(reverse, &products, model)?;
+            if let (Some(forward_rate), Some(reverse_rate)) = (forward_rate, reverse_rate) {
+                return Result::Ok(vec![
+                    Reaction::new(reactants.clone(), products.clone(), Self::as_rate(forward, forward_rate)?),
+                    Reaction::new(products, reactants, Self::as_rate(reverse, reverse_rate)?),
+                ]);
+            }
+        }
+
+        Result::Err(MarleaParserError::NotMassAction { location: rate_law.location.clone(), reaction: reaction.name.clone() })
+    }
+
+    /// rate constant of a mass action term over exactly the given species,
+    /// or None if the term is not a product of constants and those species
+    fn mass_action_rate(rate_law: &Located, species: &[Term], model: &Model) -> Result<Option<Rational>, MarleaParserError> {
+        let mut factors = Vec::new();
+        if !Self::collect_factors(rate_law, 1, &mut factors) {
+            return Result::Ok(None);
+        }
+
+        let mut mass_action = MassAction::new();
+        for (factor, power) in factors {
+            if let Expression::Identifier(name) = &factor.expression {
+                if model.species.contains(name) {
+                    mass_action.species(name, power);
+                    continue;
+                }
+                // a compartment has no default size, so one without a size can not be evaluated
+                if model.compartments.contains(name) && !model.assignments.contains_key(name) {
+                    return Result::Err(MarleaParserError::UnsupportedAntimony { location: factor.location.clone(), construct: "compartment without a size" });
+                }
+            }
+
+            let value = Self::evaluate(factor, model, &mut Vec::new())?;
+            if let Err(err) = mass_action.constant(value, power) {
+                return Result::Err(Self::arithmetic_error(err, factor.location.clone()));
+            }
+        }
+
+        Result::Ok(mass_action.rate_over(species))
+    }
+
+    /// flatten products and whole number powers of the expression tree into leaf names and numbers with the power each is raised to
+    fn collect_factors<'a>(rate_law: &'a Located, power: u64, factors: &mut Vec<(&'a Located, u64)>) -> bool {
+        match &rate_law.expression {
+            Expression::Number(_) | Expression::Identifier(_) => {
+                factors.push((rate_law, power));
+                true
+            },
+            Expression::Binary(Rule::multiply, left, right) => {
+                Self::collect_factors(left, power, factors) && Self::collect_factors(right, power, factors)
+            },
+            Expression::Binary(Rule::power, base, exponent) => match &exponent.expression {
+                Expression::Number(exponent) => match exponent.to_u64().ok().and_then(|exponent| exponent.checked_mul(power)) {
+                    Some(power) => Self::collect_factors(base, power, factors),
+                    None => false,
+                },
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn as_rate(rate_law: &Located, rate: Rational) -> Result<u64, MarleaParserError> {
+        let location = rate_law.location.clone();
+        match rate.to_u64() {
+            Ok(0) => Result::Err(MarleaParserError::ZeroRate { location, text: rate.to_string() }),
+            Ok(rate) => Result::Ok(rate),
+            Err(IntegerConversionError::NotIntegral) => Result::Err(MarleaParserError::InexactRate { location, text: rate.to_string() }),
+            Err(_) => Result::Err(MarleaParserError::BadRate { location, text: rate.to_string() }),
+        }
+    }
+
+    fn arithmetic_error(error: ArithmeticError, location: SourceSpan) -> MarleaParserError {
+        match error {
+            ArithmeticError::DivisionByZero => MarleaParserError::DivisionByZero { location },
+            ArithmeticError::Overflow => MarleaParserError::ArithmeticOverflow { location },
+            ArithmeticError::BadExponent => MarleaParserError::BadExponent { location },
+            // only number literals underflow, and as_expression reports those along with their text
+            ArithmeticError::Underflow => MarleaParserError::NonIntegral { location, text: String::from("number") },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{MarleaParser, MarleaParserError, antimony::AntimonyParser};
+
+    #[test]
+    fn antimony_parser_imports_mass_action_reactions() {
+        let parsed = MarleaParser::parse(&Path::new("test_data").join("antimony").join("toggle.ant")).unwrap();
+        let expected = MarleaParser::parse_str(concat!(
+            "A + 2 B => C,40,\n",
+            "C <=> D,2,5,\n",
+            "NULL => A,3,\n",
+            "E => NULL,1,\n",
+            "A,5,\n",
+            "B,10,\n",
+            "E,2,\n",
+        )).unwrap();
+        assert_eq!(parsed.get_reactions(), expected.get_reactions());
+        assert_eq!(parsed.get_solution().species_counts, expected.get_solution().species_counts);
+    }
+
+    #[test]
+    fn antimony_parser_rejects_other_rate_laws() {
+        let source = "J1: A -> B; k1*A/(1 + A)\nJ2: B => A; k2*A\nJ3: $S => A; k2\nat (time > 5): A = 10\nk1 = 1; k2 = 0.5\n";
+        let (network, diagnostics) = AntimonyParser::as_reaction_network_recovering(source);
+
+        let lines = diagnostics.iter()
+            .map(|diagnostic| (diagnostic.summary(), diagnostic.location().map(|location| location.line)))
+            .collect::<Vec<(String, Option<usize>)>>();
+        assert_eq!(lines, [
+            (String::from("unsupported antimony boundary species"), Some(3)),
+            (String::from("unsupported antimony event"), Some(4)),
+            (String::from("reaction `J1` does not have a mass action kinetic law"), Some(1)),
+            (String::from("reaction `J2` does not have a mass action kinetic law"), Some(2)),
+        ]);
+        assert!(network.get_reactions().is_empty());
+
+        // a rate constant which is not a whole number can not be an engine rate
+        match AntimonyParser::as_reaction_network("A => B; k*A; k = 2.5").unwrap_err() {
+            MarleaParserError::InexactRate { location, .. } => assert_eq!(location.column, 9),
+            other => panic!("expected an inexact rate, found {}", other),
+        }
+        assert!(matches!(AntimonyParser::as_reaction_network("A => B; 1e-39 / 1e-40 * A"), Err(MarleaParserError::InexactRate { .. })));
+
+        // orders and coefficients too large to add up are not mass action rather than overflowing
+        assert!(matches!(AntimonyParser::as_reaction_network("A => B; k*A^18446744073709551615*A; k = 1"), Err(MarleaParserError::NotMassAction { .. })));
+        assert!(matches!(AntimonyParser::as_reaction_network("18446744073709551615 A + A => B; k*A; k = 1"), Err(MarleaParserError::NotMassAction { .. })));
+    }
+
+    #[test]
+    fn antimony_parser_scales_by_compartment_size() {
+        let rate = |source: &str| AntimonyParser::as_reaction_network(source).map(|network| network.get_reactions().iter().next().unwrap().get_reaction_rate());
+        assert_eq!(rate("compartment cell = 1\nA -> B; cell*3*A\n").unwrap(), 3);
+        assert_eq!(rate("compartment cell = 2\nA -> B; cell*3*A\n").unwrap(), 6);
+        assert_eq!(rate("compartment cell\ncell = 4\nA -> B; cell*3*A\n").unwrap(), 12);
+        assert!(matches!(rate("compartment cell\nA -> B; cell*3*A\n"), Err(MarleaParserError::UnsupportedAntimony { construct: "compartment without a size", .. })));
+    }
+
+    #[test]
+    fn antimony_parser_reports_syntax_errors() {
+        match AntimonyParser::as_reaction_network("A + B -> C; k1*A*B\nA + => B; k1\n").unwrap_err() {
+            MarleaParserError::Antimony { location, .. } => assert_eq!(location.line, 2),
+            other => panic!("expected a syntax error, found {}", other),
+        }
+    }
+}
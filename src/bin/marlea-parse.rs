@@ -0,0 +1,243 @@
+//! Command line front end for checking and converting reaction network files.
+//!
+//! ```text
+//! marlea-parse check [--strict] <file>...         print every diagnostic, exits with 1 if any file has a problem
+//! marlea-parse convert <input> <output>           convert between formats chosen by file extension
+//! marlea-parse stats <file>                       species and reaction counts with a histogram of reaction rates
+//! marlea-parse fmt [--check] [--align] <file>...  rewrite csv networks in canonical form
+//! ```
+
+use std::{collections::{BTreeMap, HashMap}, env, fs, io::{self, IsTerminal}, path::{Path, PathBuf}, process::ExitCode};
+
+use marlea_engine::trial::reaction_network::ReactionNetwork;
+
+use MARlea_parser::{MarleaParser, MarleaParserError, diagnostic::DiagnosticRenderer, dot::DOTwriter, formatter::CSVformatter, json::JSONwriter, lint::{Linter, Severity}, sbml::SBMLwriter, writer::CSVwriter};
+
+const USAGE: &str = "\
+usage: marlea-parse <command> [arguments]
+
+commands:
+    check [--strict] <file>...
+                              print every diagnostic, exits with 1 if any file has a problem,
+                              --strict also reports duplicate rows, dead and unused species and
+                              likely misspelled names in csv files
+    convert <input> <output>  convert between formats chosen by file extension
+    stats <file>              species and reaction counts with a histogram of reaction rates
+    fmt [--check] [--align] <file>...
+                              rewrite csv networks in canonical form, --check only lists files which
+                              would change and exits with 1 if there are any, --align lines up rates
+
+input formats: csv, xml, sbml, ant, net, json, toml, yaml, yml
+output formats: csv, xml, sbml, json, toml, yaml, yml, dot
+";
+
+fn main() -> ExitCode {
+    let arguments = env::args().skip(1).collect::<Vec<String>>();
+    let arguments = arguments.iter().map(String::as_str).collect::<Vec<&str>>();
+
+    let result = match arguments.as_slice() {
+        ["check", options @ ..] => check(options),
+        ["convert", input, output] => convert(Path::new(input), Path::new(output)),
+        ["stats", path] => stats(Path::new(path)),
+        ["fmt", options @ ..] => fmt(options),
+        ["help" | "--help" | "-h"] => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        },
+        _ => {
+            eprint!("{}", USAGE);
+            return ExitCode::from(2);
+        },
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+/// parse every file reporting all of their diagnostics rather than stopping at the first,
+/// with `--strict` files which parse are also linted
+fn check(options: &[&str]) -> Result<ExitCode, String> {
+    let strict = options.contains(&"--strict");
+    let paths = match as_paths(options, &["--strict"]) {
+        Ok(paths) => paths,
+        Err(code) => return Ok(code),
+    };
+
+    let linter = Linter::new().dead_species(Severity::Warn).unused_species(Severity::Warn).near_misses(Severity::Warn);
+    let mut sources = HashMap::new();
+    let mut problems = 0;
+    for path in &paths {
+        let diagnostics = match MarleaParser::parse_recovering(Path::new(path)) {
+            Ok((_, diagnostics)) if diagnostics.is_empty() && strict => match linter.lint(Path::new(path)) {
+                Ok((_, warnings)) => warnings,
+                Err(err) => vec![err],
+            },
+            Ok((_, diagnostics)) => diagnostics,
+            Err(err) => vec![err],
+        };
+        for diagnostic in &diagnostics {
+            eprintln!("{}", render(diagnostic, &mut sources));
+        }
+        problems += diagnostics.len();
+    }
+
+    eprintln!("checked {} file(s), found {} problem(s)", paths.len(), problems);
+    match problems {
+        0 => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::FAILURE),
+    }
+}
+
+/// read a network in any supported format and write it in the format named by the output extension
+fn convert(input: &Path, output: &Path) -> Result<ExitCode, String> {
+    let reaction_network = parse(input)?;
+    let text = as_format(&reaction_network, output)?;
+    fs::write(output, text).map_err(|err| format!("error: failed to write {}: {}", output.display(), err))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn stats(path: &Path) -> Result<ExitCode, String> {
+    let reaction_network = parse(path)?;
+    print!("{}", as_stats(&reaction_network));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// format csv files in place, or with `--check` list the files which are not formatted
+fn fmt(options: &[&str]) -> Result<ExitCode, String> {
+    let check = options.contains(&"--check");
+    let formatter = CSVformatter::new().align_rates(options.contains(&"--align"));
+    let paths = match as_paths(options, &["--check", "--align"]) {
+        Ok(paths) => paths,
+        Err(code) => return Ok(code),
+    };
+
+    let mut unformatted = 0;
+    for path in paths {
+        let path = Path::new(path);
+        if path.extension().and_then(|ext| ext.to_str()) != Some("csv") {
+            return Err(format!("error: {} is not a csv file", path.display()));
+        }
+
+        let source = MarleaParser::read_source(path).map_err(|err| render(&err, &mut HashMap::new()))?;
+        let formatted = formatter.format(&source).map_err(|err| render(&err.with_path(path), &mut HashMap::new()))?;
+        match (formatted == source, check) {
+            (true, _) => (),
+            (false, true) => {
+                println!("{}", path.display());
+                unformatted += 1;
+            },
+            (false, false) => fs::write(path, formatted).map_err(|err| format!("error: failed to write {}: {}", path.display(), err))?,
+        }
+    }
+
+    match unformatted {
+        0 => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::FAILURE),
+    }
+}
+
+/// the paths given to a command, printing the usage and failing with exit code 2
+/// if there are none or an option other than the allowed ones was given
+fn as_paths<'a>(options: &[&'a str], allowed: &[&str]) -> Result<Vec<&'a str>, ExitCode> {
+    let mut paths = Vec::new();
+    for option in options {
+        match option.starts_with("--") {
+            true if allowed.contains(option) => (),
+            true => {
+                eprint!("error: unknown option {}\n\n{}", option, USAGE);
+                return Err(ExitCode::from(2));
+            },
+            false => paths.push(*option),
+        }
+    }
+
+    if paths.is_empty() {
+        eprint!("{}", USAGE);
+        return Err(ExitCode::from(2));
+    }
+    Ok(paths)
+}
+
+fn parse(path: &Path) -> Result<ReactionNetwork, String> {
+    MarleaParser::parse(path).map_err(|err| render(&err, &mut HashMap::new()))
+}
+
+/// the network as text in the format named by the extension of a path
+fn as_format(reaction_network: &ReactionNetwork, path: &Path) -> Result<String, String> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => Ok(CSVwriter::as_csv(reaction_network)),
+        Some("xml" | "sbml") => Ok(SBMLwriter::as_sbml(reaction_network)),
+        Some("json") => Ok(JSONwriter::as_json(reaction_network)),
+        Some("toml") => toml::to_string(&JSONwriter::as_document(reaction_network)).map_err(|err| format!("error: can not write toml: {}", err)),
+        Some("yaml" | "yml") => serde_norway::to_string(&JSONwriter::as_document(reaction_network)).map_err(|err| format!("error: can not write yaml: {}", err)),
+        Some("dot") => Ok(DOTwriter::new().as_dot(reaction_network)),
+        _ => Err(format!("error: can not write {}, supported output formats are: csv, xml, sbml, json, toml, yaml, yml, dot", path.display())),
+    }
+}
+
+/// species and reaction counts followed by how many reactions have each rate
+fn as_stats(reaction_network: &ReactionNetwork) -> String {
+    let mut histogram: BTreeMap<u64, usize> = BTreeMap::new();
+    for reaction in reaction_network.get_reactions() {
+        *histogram.entry(reaction.get_reaction_rate()).or_default() += 1;
+    }
+
+    let mut output = format!("species: {}\nreactions: {}\nrates:\n", reaction_network.get_solution().species_counts.len(), reaction_network.get_reactions().len());
+    let width = histogram.keys().map(|rate| rate.to_string().len()).max().unwrap_or(0);
+    for (rate, count) in histogram {
+        output.push_str(&format!("    {:>width$}: {}\n", rate, count, width = width));
+    }
+    output
+}
+
+/// render a diagnostic against the source of the file it points into, reading each file at most once
+fn render(error: &MarleaParserError, sources: &mut HashMap<PathBuf, String>) -> String {
+    let source = match error.path() {
+        Some(path) => sources.entry(path.to_path_buf())
+            .or_insert_with(|| MarleaParser::read_source(path).unwrap_or_default())
+            .as_str(),
+        None => "",
+    };
+    DiagnosticRenderer::new().color(io::stderr().is_terminal()).render(error, source)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use MARlea_parser::MarleaParser;
+
+    use crate::{as_format, as_paths, as_stats};
+
+    #[test]
+    fn stats_counts_rates() {
+        let network = MarleaParser::parse_str("A => B,10,\nB => A,10,\nA + B => C,1000,\nA,5,\n").unwrap();
+        assert_eq!(as_stats(&network), concat!(
+            "species: 3\n",
+            "reactions: 3\n",
+            "rates:\n",
+            "      10: 2\n",
+            "    1000: 1\n",
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        assert_eq!(as_paths(&["--strict", "a.csv", "b.csv"], &["--strict"]).unwrap(), ["a.csv", "b.csv"]);
+        assert!(as_paths(&["--stirct", "a.csv"], &["--strict"]).is_err());
+        assert!(as_paths(&["--strict"], &["--strict"]).is_err());
+    }
+
+    #[test]
+    fn converts_by_output_extension() {
+        let network = MarleaParser::parse_str("A => B,10,\nA,5,\n").unwrap();
+        assert!(as_format(&network, Path::new("network.dot")).unwrap().starts_with("digraph"));
+        assert!(as_format(&network, Path::new("network.toml")).unwrap().contains("[[reactions]]"));
+        assert!(as_format(&network, Path::new("network.net")).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+//! Reads flattened reaction networks generated by BioNetGen from `.net` files.
+//!
+//! Only the `parameters`, `species` and `reactions` blocks are interpreted, `groups` and other descriptive blocks are skipped.
+//! Parameters are evaluated in order with the same expression rules as csv `#param` rows,
+//! so a parameter may use any parameter declared above it.
+//! Species are named by their BioNetGen pattern such as `A(b!1).B(a!1)`, unless the pattern contains characters a csv name
+//! can not, such as the comma in `A(b,c)`, in which case the species is named by its index as `S4`.
+//! Reactions refer to species by index with `0` standing for no species, and their rate law must evaluate to a whole number.
+//!
+//! ```text
+//! begin parameters
+//!     1 kp1  10
+//!     2 km1  kp1/5
+//! end parameters
+//! begin species
+//!     1 A(b)           100
+//!     2 B(a)           50
+//!     3 A(b!1).B(a!1)  0
+//! end species
+//! begin reactions
+//!     1 1,2 3 kp1  #_R1
+//!     2 3 1,2 km1  #_reverse__R1
+//! end reactions
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, solution::{Name, Count, Solution}, reaction::{Reaction, term::Term}};
+
+use crate::{CSVparser, MarleaParserError, Parameter, SourceSpan, number::{IntegerConversionError, Rational}};
+
+/// Interprets BioNetGen network files as reaction networks
+pub(crate) struct BNGparser;
+
+/// the block of a network file a line belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Outside,
+    Parameters,
+    Species,
+    Reactions,
+    // blocks which describe the network without changing it, such as groups
+    Skipped,
+}
+
+/// a whitespace separated field of a line and the byte offset it starts at within the whole source
+type Field<'a> = (usize, &'a str);
+
+/// everything read from the network so far
+#[derive(Default)]
+struct Network {
+    parameters: HashMap<String, Parameter>,
+    // species names by their index in the species block
+    species: HashMap<String, Name>,
+    species_counts: HashMap<Name, Count>,
+    reactions: HashSet<Reaction>,
+}
+
+impl BNGparser {
+    /// parse a BioNetGen network into a reaction network, failing on the first line which can not be imported
+    pub(crate) fn as_reaction_network(source: &str) -> Result<ReactionNetwork, MarleaParserError> {
+        let (reaction_network, mut diagnostics) = Self::as_reaction_network_recovering(source);
+        match diagnostics.is_empty() {
+            true => Result::Ok(reaction_network),
+            false => Result::Err(diagnostics.remove(0)),
+        }
+    }
+
+    /// parse a BioNetGen network into a reaction network, skipping any line which can not be imported.
+    /// Returns the network built from every valid line along with a diagnostic for each invalid one.
+    pub(crate) fn as_reaction_network_recovering(source: &str) -> (ReactionNetwork, Vec<MarleaParserError>) {
+        let mut network = Network::default();
+        let mut diagnostics = Vec::new();
+        let mut block = Block::Outside;
+
+        let mut line_start = 0;
+        for line in source.split_inclusive('\n') {
+            // everything after a hash is a comment
+            let text = line.split('#').next().unwrap_or_default();
+            let fields = Self::fields(text, line_start);
+            line_start += line.len();
+
+            let result = match (fields.first(), block) {
+                (None, _) => Result::Ok(()),
+                (Some((_, "begin")), _) => Self::begin_block(source, &fields).map(|begun| block = begun),
+                (Some((_, "end")), _) => {
+                    block = Block::Outside;
+                    Result::Ok(())
+                },
+                (Some(&(offset, text)), Block::Outside) => Result::Err(MarleaParserError::InvalidBionetgen { location: Self::span(source, offset, text), reason: String::from("expected a `begin` line") }),
+                (Some(_), Block::Parameters) => Self::interpret_parameter(source, &fields, &mut network),
+                (Some(_), Block::Species) => Self::interpret_species(source, &fields, &mut network),
+                (Some(_), Block::Reactions) => Self::interpret_reaction(source, &fields, &mut network),
+                (Some(_), Block::Skipped) => Result::Ok(()),
+            };
+            if let Err(msg) = result {
+                diagnostics.push(msg);
+            }
+        }
+
+        // any species used by a reaction but never given a count still needs one
+        let mut species_counts = network.species_counts;
+        for reaction in &network.reactions {
+            for term in reaction.get_reactants().iter().chain(reaction.get_products()) {
+                species_counts.entry(term.get_species_name().clone()).or_insert(Count(0));
+            }
+        }
+
+        (ReactionNetwork::new(network.reactions, Solution { species_counts }), diagnostics)
+    }
+
+    /// the block started by a begin line, block names such as `molecule types` may be more than one word
+    fn begin_block(source: &str, fields: &[Field]) -> Result<Block, MarleaParserError> {
+        let name = fields[1..].iter().map(|(_, text)| *text).collect::<Vec<&str>>().join(" ");
+        let (offset, text) = fields[fields.len() - 1];
+        match name.as_str() {
+            "parameters" => Result::Ok(Block::Parameters),
+            "species" => Result::Ok(Block::Species),
+            "reactions" => Result::Ok(Block::Reactions),
+            "groups" | "observables" | "molecule types" | "compartments" => Result::Ok(Block::Skipped),
+            "functions" => Result::Err(MarleaParserError::UnsupportedBionetgen { location: Self::span(source, offset, text), construct: "functions" }),
+            _ => Result::Err(MarleaParserError::InvalidBionetgen { location: Self::span(source, fields[0].0, text), reason: format!("unknown block `{}`", name) }),
+        }
+    }
+
+    /// `index name value`
+    fn interpret_parameter(source: &str, fields: &[Field], network: &mut Network) -> Result<(), MarleaParserError> {
+        let (name, value) = match fields {
+            [_, name, value] => (name, value),
+            _ => return Result::Err(Self::malformed(source, fields, "expected a parameter index, name and value")),
+        };
+        let location = Self::span(source, name.0, name.1);
+        if network.parameters.contains_key(name.1) {
+            let previous = network.parameters[name.1].location.clone();
+            return Result::Err(MarleaParserError::DuplicateParameter { location, name: name.1.to_string(), previous: Box::new(previous) });
+        }
+
+        let value = Self::evaluate(source, value, &network.parameters)?;
+        network.parameters.insert(name.1.to_string(), Parameter { value, location });
+        Result::Ok(())
+    }
+
+    /// `index pattern count`
+    fn interpret_species(source: &str, fields: &[Field], network: &mut Network) -> Result<(), MarleaParserError> {
+        let (index, pattern, count) = match fields {
+            [index, pattern, count] => (index, pattern, count),
+            _ => return Result::Err(Self::malformed(source, fields, "expected a species index, pattern and count")),
+        };
+        // fixed species keep a constant population which a reaction network can not express
+        if pattern.1.starts_with('$') {
+            return Result::Err(MarleaParserError::UnsupportedBionetgen { location: Self::span(source, pattern.0, pattern.1), construct: "fixed species" });
+        }
+
+        let name = match CSVparser::is_name(pattern.1) {
+            true => Name(pattern.1.to_string()),
+            false => Name(format!("S{}", index.1)),
+        };
+        network.species.insert(index.1.to_string(), name.clone());
+
+        let location = Self::span(source, count.0, count.1);
+        let count = match Self::evaluate(source, count, &network.parameters)?.to_u64() {
+            Ok(count) => Count(count),
+            Err(IntegerConversionError::NotIntegral) => return Result::Err(MarleaParserError::NonIntegral { location, text: count.1.to_string() }),
+            Err(_) => return Result::Err(MarleaParserError::BadSpeciesCount { location, text: count.1.to_string() }),
+        };
+        network.species_counts.insert(name, count);
+        Result::Ok(())
+    }
+
+    /// `index reactants products rate`
+    fn interpret_reaction(source: &str, fields: &[Field], network: &mut Network) -> Result<(), MarleaParserError> {
+        let (reactants, products, rate) = match fields {
+            [_, reactants, products, rate] => (reactants, products, rate),
+            _ => return Result::Err(Self::malformed(source, fields, "expected a reaction index, reactants, products and rate")),
+        };
+        let reactants = Self::as_terms(source, reactants, network)?;
+        let products = Self::as_terms(source, products, network)?;
+
+        let location = Self::span(source, rate.0, rate.1);
+        let rate = match Self::evaluate(source, rate, &network.parameters) {
+            Ok(rate) => rate,
+            // rate laws which are not expressions, such as Sat(k, Km), are functional rate laws
+            Err(MarleaParserError::InvalidBionetgen { location, .. }) => return Result::Err(MarleaParserError::UnsupportedBionetgen { location, construct: "rate law" }),
+            Err(msg) => return Result::Err(msg.into_rate_error()),
+        };
+        let rate = match rate.to_u64() {
+            Ok(0) => return Result::Err(MarleaParserError::ZeroRate { location, text: rate.to_string() }),
+            Ok(rate) => rate,
+            Err(IntegerConversionError::NotIntegral) => return Result::Err(MarleaParserError::InexactRate { location, text: rate.to_string() }),
+            Err(_) => return Result::Err(MarleaParserError::BadRate { location, text: rate.to_string() }),
+        };
+
+        network.reactions.insert(Reaction::new(reactants, products, rate));
+        Result::Ok(())
+    }
+
+    /// comma separated species indices, repeated indices become a coefficient and 0 is no species
+    fn as_terms(source: &str, field: &Field, network: &Network) -> Result<Vec<Term>, MarleaParserError> {
+        let mut terms: Vec<(Name, u64)> = Vec::new();
+        let mut offset = field.0;
+        for index in field.1.split(',') {
+            let location = Self::span(source, offset, index);
+            offset += index.len() + 1;
+            if index == "0" {
+                continue;
+            }
+
+            let name = match network.species.get(index) {
+                Some(name) => name,
+                None => return Result::Err(MarleaParserError::InvalidBionetgen { location, reason: format!("species {} is not declared", index) }),
+            };
+            match terms.iter_mut().find(|(term, _)| term == name) {
+                Some((_, coefficient)) => *coefficient += 1,
+                None => terms.push((name.clone(), 1)),
+            }
+        }
+
+        Result::Ok(terms.into_iter().map(|(name, coefficient)| Term::new(name, Count(coefficient))).collect())
+    }
+
+    /// evaluate a field with the csv expression rules, reporting errors at their place in the whole source
+    fn evaluate(source: &str, field: &Field, parameters: &HashMap<String, Parameter>) -> Result<Rational, MarleaParserError> {
+        match CSVparser::evaluate_text(field.1, parameters) {
+            Some(value) => value.map_err(|err| err.offset_by(source, field.0)),
+            None => Result::Err(MarleaParserError::InvalidBionetgen { location: Self::span(source, field.0, field.1), reason: String::from("expected a number, parameter or expression") }),
+        }
+    }
+
+    /// whitespace separated fields of a line along with the offset of each within the whole source
+    fn fields(line: &str, line_start: usize) -> Vec<Field<'_>> {
+        let mut fields = Vec::new();
+        let mut field_start = None;
+        for (index, character) in line.char_indices() {
+            match (character.is_whitespace(), field_start) {
+                (true, Some(start)) => {
+                    fields.push((line_start + start, &line[start..index]));
+                    field_start = None;
+                },
+                (false, None) => field_start = Some(index),
+                _ => (),
+            }
+        }
+        if let Some(start) = field_start {
+            fields.push((line_start + start, &line[start..]));
+        }
+        fields
+    }
+
+    fn malformed(source: &str, fields: &[Field], reason: &str) -> MarleaParserError {
+        let (start, _) = fields[0];
+        let (last, text) = fields[fields.len() - 1];
+        let mut location = SourceSpan::from_offset(source, start);
+        location.end = last + text.len();
+        MarleaParserError::InvalidBionetgen { location, reason: reason.to_string() }
+    }
+
+    fn span(source: &str, offset: usize, text: &str) -> SourceSpan {
+        let mut location = SourceSpan::from_offset(source, offset);
+        location.end = offset + text.len();
+        location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{MarleaParser, MarleaParserError, bionetgen::BNGparser};
+
+    #[test]
+    fn bionetgen_parser_imports_flattened_networks() {
+        let parsed = MarleaParser::parse(&Path::new("test_data").join("bionetgen").join("dimer.net")).unwrap();
+        let expected = MarleaParser::parse_str(concat!(
+            "A(b) + B(a) => A(b!1).B(a!1),10,\n",
+            "A(b!1).B(a!1) => A(b) + B(a),2,\n",
+            "2 A(b) => S4,10,\n",
+            "NULL => A(b),3,\n",
+            "S4 => NULL,1,\n",
+            "A(b),100,\n",
+            "B(a),50,\n",
+            "S4,2,\n",
+        )).unwrap();
+        assert_eq!(parsed.get_reactions(), expected.get_reactions());
+        assert_eq!(parsed.get_solution().species_counts, expected.get_solution().species_counts);
+    }
+
+    #[test]
+    fn bionetgen_parser_reports_located_errors() {
+        let source = concat!(
+            "begin parameters\n",
+            "    1 k1 2.5\n",
+            "    2 k2 k3*2\n",
+            "end parameters\n",
+            "begin species\n",
+            "    1 A() 10\n",
+            "end species\n",
+            "begin reactions\n",
+            "    1 1 2 k1\n",
+            "    2 1 0 Sat(k1,10)\n",
+            "    3 1 0 k1\n",
+            "end reactions\n",
+        );
+        let (_, diagnostics) = BNGparser::as_reaction_network_recovering(source);
+
+        let found = diagnostics.iter()
+            .map(|diagnostic| (diagnostic.summary(), diagnostic.location().map(|location| (location.line, location.column))))
+            .collect::<Vec<(String, Option<(usize, usize)>)>>();
+        assert_eq!(found, [
+            (String::from("undefined parameter `k3`"), Some((3, 10))),
+            (String::from("invalid BioNetGen network"), Some((9, 9))),
+            (String::from("unsupported BioNetGen rate law"), Some((10, 11))),
+            (String::from("reaction rate is not a whole number"), Some((11, 11))),
+        ]);
+        assert!(matches!(&diagnostics[1], MarleaParserError::InvalidBionetgen { reason, .. } if reason == "species 2 is not declared"));
+    }
+}
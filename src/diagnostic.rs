@@ -0,0 +1,146 @@
+//! Renders parser errors as annotated source snippets in the style of rustc
+//!
+//! ```text
+//! error: could not parse row
+//!  --> network.csv:3:12
+//!   |
+//! 3 | A + B => C,,
+//!   |            ^ expected expression
+//!   |
+//!   = help: rates and counts are numbers, parameters or expressions such as 10, 2.5e3, fast or fast * 10
+//! ```
+
+use crate::{MarleaParserError, SourceSpan};
+
+// ansi escape codes used when rendering with color
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders a [MarleaParserError] along with the line of source it points at.
+/// Plain text by default, with ansi colors when enabled for terminal output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiagnosticRenderer {
+    color: bool,
+}
+
+impl DiagnosticRenderer {
+    pub fn new() -> Self {
+        DiagnosticRenderer { color: false }
+    }
+
+    /// enable or disable ansi colors in rendered output
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// render an error as an annotated snippet of source,
+    /// source must be the decoded text of the file the error came from
+    pub fn render(&self, error: &MarleaParserError, source: &str) -> String {
+        let mut output = format!("{}error{}{}: {}{}\n", self.style(RED), self.style(RESET), self.style(BOLD), error.summary(), self.style(RESET));
+
+        match error.location() {
+            Some(location) => self.render_snippet(&mut output, location, &error.label(), source, error.help()),
+            None => {
+                if let Some(path) = error.path() {
+                    output.push_str(&format!("{}-->{} {}\n", self.style(BLUE), self.style(RESET), path.display()));
+                }
+                if let Some(help) = error.help() {
+                    output.push_str(&format!("{}={} {}help{}: {}\n", self.style(BLUE), self.style(RESET), self.style(BOLD), self.style(RESET), help));
+                }
+            }
+        }
+
+        output
+    }
+
+    /// render every error in order separated by blank lines
+    pub fn render_all(&self, errors: &[MarleaParserError], source: &str) -> String {
+        errors.iter()
+        .map(|error| self.render(error, source))
+        .collect::<Vec<String>>()
+        .join("\n")
+    }
+
+    fn render_snippet(&self, output: &mut String, location: &SourceSpan, label: &str, source: &str, help: Option<String>) {
+        let gutter_width = location.line.to_string().len();
+        let gutter = " ".repeat(gutter_width);
+        let file = match &location.path {
+            Some(path) => path.display().to_string(),
+            None => String::from("<input>"),
+        };
+
+        // find the full line containing the start of the span
+        let start = floor_char_boundary(source, location.start);
+        let line_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |newline| start + newline);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+
+        // underline the span, clamped to the first line it covers
+        let end = floor_char_boundary(source, location.end.clamp(start, line_start + line.len()));
+        let padding = source[line_start..start].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect::<String>();
+        let underline = "^".repeat(source[start..end].chars().count().max(1));
+
+        output.push_str(&format!("{}{}-->{} {}:{}:{}\n", gutter, self.style(BLUE), self.style(RESET), file, location.line, location.column));
+        output.push_str(&format!("{} {}|{}\n", gutter, self.style(BLUE), self.style(RESET)));
+        output.push_str(&format!("{}{} |{} {}\n", self.style(BLUE), location.line, self.style(RESET), line));
+        output.push_str(&format!("{} {}|{} {}{}{}", gutter, self.style(BLUE), self.style(RESET), padding, self.style(RED), underline));
+        if !label.is_empty() {
+            output.push(' ');
+            output.push_str(label);
+        }
+        output.push_str(&format!("{}\n", self.style(RESET)));
+
+        if let Some(help) = help {
+            output.push_str(&format!("{} {}|{}\n", gutter, self.style(BLUE), self.style(RESET)));
+            output.push_str(&format!("{} {}={} {}help{}: {}\n", gutter, self.style(BLUE), self.style(RESET), self.style(BOLD), self.style(RESET), help));
+        }
+    }
+
+    fn style(&self, code: &'static str) -> &'static str {
+        if self.color { code } else { "" }
+    }
+}
+
+/// largest char boundary in source at or before index
+fn floor_char_boundary(source: &str, index: usize) -> usize {
+    let mut index = index.min(source.len());
+    while !source.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CSVparser, diagnostic::DiagnosticRenderer};
+
+    #[test]
+    fn renders_plain_snippet() {
+        let input = "A + B => C,10,\nA + B => C,,\n";
+        let error = CSVparser::as_reaction_network(input).unwrap_err();
+
+        let rendered = DiagnosticRenderer::new().render(&error, input);
+        assert_eq!(rendered, concat!(
+            "error: could not parse row\n",
+            " --> <input>:2:12\n",
+            "  |\n",
+            "2 | A + B => C,,\n",
+            "  |            ^ expected expression\n",
+            "  |\n",
+            "  = help: rates and counts are numbers, parameters or expressions such as 10, 2.5e3, fast or fast * 10\n",
+        ));
+    }
+
+    #[test]
+    fn renders_colored_snippet() {
+        let input = "99999999999999999999999 A => B,1,\n";
+        let error = CSVparser::as_reaction_network(input).unwrap_err();
+
+        let rendered = DiagnosticRenderer::new().color(true).render(&error, input);
+        assert!(rendered.contains("\x1b[1;31m^^^^^^^^^^^^^^^^^^^^^^^ coefficient is not a positive count\x1b[0m"));
+        assert!(rendered.starts_with("\x1b[1;31merror"));
+    }
+}
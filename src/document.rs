@@ -0,0 +1,344 @@
+//! Reads reaction networks from structured json, toml and yaml documents.
+//!
+//! Every format is decoded into the same tree of values and interpreted against one schema,
+//! see [crate::json] for the json form. Documents build the same intermediate network as csv files,
+//! and numbers are evaluated and converted by the csv parser from their shortest decimal form,
+//! so species names, counts, coefficients and rates are validated identically whichever format they come from.
+//! Decoded values have no position in the source, so a number which is rejected is reported
+//! at the start of the document along with its path such as `$.reactions[2].rate`.
+//!
+//! Hand written documents may also list species as a table of names to counts,
+//! and may carry a `metadata` value of any shape which is ignored.
+//!
+//! ```toml
+//! # toggle switch
+//! [metadata]
+//! author = "lab"
+//!
+//! [species]
+//! A = 5
+//! "setup.call" = 1
+//!
+//! [[reactions]]
+//! reactants = [{ name = "A" }, { name = "B", coefficient = 2 }]
+//! products = [{ name = "C" }]
+//! rate = 10
+//! ```
+//!
+//! ```yaml
+//! species:
+//!   A: 5
+//! reactions:
+//!   - reactants: [{ name: A }, { name: B, coefficient: 2 }]
+//!     products: [{ name: C }]
+//!     rate: 10 # per second
+//! ```
+
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, solution::{Name, Count}, reaction::{Reaction, term::Term}};
+
+use crate::{CSVparser, MarleaParserError, NetworkBuilder, SourceSpan, number::Rational};
+
+/// text formats a structured network document may be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DocumentFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+/// Interprets json, toml and yaml documents as reaction networks
+pub(crate) struct DocumentParser;
+
+impl DocumentParser {
+    /// parse a document into a reaction network, failing on the first value which does not match the schema
+    pub(crate) fn as_reaction_network(format: DocumentFormat, source: &str) -> Result<ReactionNetwork, MarleaParserError> {
+        let (reaction_network, mut diagnostics) = Self::as_reaction_network_recovering(format, source);
+        match diagnostics.is_empty() {
+            true => Result::Ok(reaction_network),
+            false => Result::Err(diagnostics.remove(0)),
+        }
+    }
+
+    /// parse a document into a reaction network, skipping any species or reaction which does not match the schema.
+    /// Returns the network built from every valid entry along with a diagnostic for each invalid value.
+    pub(crate) fn as_reaction_network_recovering(format: DocumentFormat, source: &str) -> (ReactionNetwork, Vec<MarleaParserError>) {
+        let mut builder = NetworkBuilder::default();
+        let mut diagnostics = Vec::new();
+
+        match Self::decode(format, source) {
+            Ok(document) => Self::interpret_document(&document, &mut builder, &mut diagnostics),
+            Err(err) => diagnostics.push(err),
+        }
+
+        (builder.build(), diagnostics)
+    }
+
+    /// decode source text of any format into a tree of values
+    fn decode(format: DocumentFormat, source: &str) -> Result<Value, MarleaParserError> {
+        match format {
+            DocumentFormat::Json => serde_json::from_str(source).map_err(|err| {
+                let location = SourceSpan::from_line_column(source, err.line(), err.column());
+                MarleaParserError::Json { location, source: Box::new(err) }
+            }),
+            DocumentFormat::Toml => toml::from_str(source).map_err(|err| {
+                let span = err.span().unwrap_or(source.len()..source.len());
+                let mut location = SourceSpan::from_offset(source, span.start);
+                location.end = span.end;
+                MarleaParserError::Toml { location, source: Box::new(err) }
+            }),
+            DocumentFormat::Yaml => serde_norway::from_str(source).map_err(|err| {
+                let offset = err.location().map_or(source.len(), |location| location.index());
+                MarleaParserError::Yaml { location: SourceSpan::from_offset(source, offset.min(source.len())), source: Box::new(err) }
+            }),
+        }
+    }
+
+    fn interpret_document(document: &Value, builder: &mut NetworkBuilder, diagnostics: &mut Vec<MarleaParserError>) {
+        let root = match document.as_object() {
+            Some(root) => root,
+            None => {
+                diagnostics.push(Self::invalid("$", "expected an object with species and reactions"));
+                return;
+            }
+        };
+
+        for (key, value) in root {
+            let path = Self::member("$", key);
+            match key.as_str() {
+                "species" => Self::interpret_species(value, &path, builder, diagnostics),
+                "reactions" => for (index, reaction) in Self::array(value, &path, diagnostics).iter().enumerate() {
+                    match Self::as_reaction(reaction, &format!("{}[{}]", path, index)) {
+                        Ok(reaction) => builder.add_reaction(reaction),
+                        Err(msg) => diagnostics.push(msg),
+                    }
+                },
+                "metadata" => (),
+                _ => diagnostics.push(Self::invalid(&path, "unknown field, expected species, reactions or metadata")),
+            }
+        }
+    }
+
+    /// species are either an array of {name, count} objects or a table of names to counts
+    fn interpret_species(value: &Value, path: &str, builder: &mut NetworkBuilder, diagnostics: &mut Vec<MarleaParserError>) {
+        let species_counts = match value {
+            Value::Object(table) => table.iter()
+                .map(|(name, count)| {
+                    let path = Self::member(path, name);
+                    Ok((Self::as_name(&Value::String(name.clone()), &path)?, Self::as_count(count, &path)?))
+                })
+                .collect::<Vec<Result<(Name, Count), MarleaParserError>>>(),
+            Value::Array(species) => species.iter()
+                .enumerate()
+                .map(|(index, species)| Self::as_species_count(species, &format!("{}[{}]", path, index)))
+                .collect(),
+            _ => vec![Result::Err(Self::invalid(path, "expected an array of species or a table of species counts"))],
+        };
+
+        for species_count in species_counts {
+            match species_count {
+                Ok(species_count) => builder.add_species_count(species_count),
+                Err(msg) => diagnostics.push(msg),
+            }
+        }
+    }
+
+    fn as_species_count(value: &Value, path: &str) -> Result<(Name, Count), MarleaParserError> {
+        let species = Self::object(value, path, &["name", "count"])?;
+        let name = Self::as_name(Self::field(species, path, "name")?, &Self::member(path, "name"))?;
+        let count = Self::as_count(Self::field(species, path, "count")?, &Self::member(path, "count"))?;
+
+        Result::Ok((name, count))
+    }
+
+    fn as_count(value: &Value, path: &str) -> Result<Count, MarleaParserError> {
+        let (count, location, text) = Self::as_number(value, path)?;
+        CSVparser::as_initial_count(count, location, text)
+    }
+
+    fn as_reaction(value: &Value, path: &str) -> Result<Reaction, MarleaParserError> {
+        let reaction = Self::object(value, path, &["reactants", "products", "rate"])?;
+        let reactants = Self::as_terms(reaction.get("reactants"), &Self::member(path, "reactants"))?;
+        let products = Self::as_terms(reaction.get("products"), &Self::member(path, "products"))?;
+        let (rate, location, text) = Self::as_number(Self::field(reaction, path, "rate")?, &Self::member(path, "rate")).map_err(MarleaParserError::into_rate_error)?;
+        let rate = CSVparser::as_rate(rate, location, text)?;
+
+        Result::Ok(Reaction::new(reactants, products, rate))
+    }
+
+    /// a missing list of terms is a side with no terms
+    fn as_terms(value: Option<&Value>, path: &str) -> Result<Vec<Term>, MarleaParserError> {
+        let terms = match value {
+            Some(Value::Array(terms)) => terms,
+            Some(_) => return Result::Err(Self::invalid(path, "expected an array of terms")),
+            None => return Result::Ok(Vec::new()),
+        };
+
+        terms.iter()
+        .enumerate()
+        .map(|(index, term)| Self::as_term(term, &format!("{}[{}]", path, index)))
+        .collect()
+    }
+
+    fn as_term(value: &Value, path: &str) -> Result<Term, MarleaParserError> {
+        let term = Self::object(value, path, &["name", "coefficient"])?;
+        let name = Self::as_name(Self::field(term, path, "name")?, &Self::member(path, "name"))?;
+        let coefficient = match term.get("coefficient") {
+            Some(coefficient) => {
+                let (coefficient, location, text) = Self::as_number(coefficient, &Self::member(path, "coefficient"))?;
+                CSVparser::as_coefficient(coefficient, location, text)?
+            },
+            None => Count(1),
+        };
+
+        Result::Ok(Term::new(name, coefficient))
+    }
+
+    /// species names are checked against the csv grammar so any network can be written back out as csv
+    fn as_name(value: &Value, path: &str) -> Result<Name, MarleaParserError> {
+        match value.as_str() {
+            Some(name) if CSVparser::is_name(name) => Result::Ok(Name(name.to_string())),
+            Some(_) => Result::Err(Self::invalid(path, "species names must not be empty or NULL, or contain whitespace, commas, `+`, `=>` or `//`")),
+            None => Result::Err(Self::invalid(path, "expected a species name")),
+        }
+    }
+
+    /// a number evaluated by the csv parser from its shortest decimal form, so floats such as 10.0 or 1e19 have the exact value they would in a csv file.
+    /// Returns the value along with the location and text to report it by
+    fn as_number(value: &Value, path: &str) -> Result<(Rational, SourceSpan, String), MarleaParserError> {
+        let number = match value {
+            Value::Number(number) => number.to_string(),
+            _ => return Result::Err(Self::invalid(path, "expected a number")),
+        };
+        let location = SourceSpan::from_offset("", 0);
+        match CSVparser::evaluate_text(&number, &HashMap::new()) {
+            Some(Ok(value)) => Result::Ok((value, location, format!("{} = {}", path, number))),
+            Some(Err(err)) => Result::Err(err.relocate(location)),
+            None => Result::Err(Self::invalid(path, "expected a number")),
+        }
+    }
+
+    /// an object containing only the given fields
+    fn object<'a>(value: &'a Value, path: &str, fields: &[&str]) -> Result<&'a Map<String, Value>, MarleaParserError> {
+        let object = match value.as_object() {
+            Some(object) => object,
+            None => return Result::Err(Self::invalid(path, &format!("expected an object with fields {}", fields.join(", ")))),
+        };
+        match object.keys().find(|key| !fields.contains(&key.as_str())) {
+            Some(key) => Result::Err(Self::invalid(&Self::member(path, key), &format!("unknown field, expected one of {}", fields.join(", ")))),
+            None => Result::Ok(object),
+        }
+    }
+
+    fn field<'a>(object: &'a Map<String, Value>, path: &str, field: &str) -> Result<&'a Value, MarleaParserError> {
+        match object.get(field) {
+            Some(value) => Result::Ok(value),
+            None => Result::Err(Self::invalid(path, &format!("missing field `{}`", field))),
+        }
+    }
+
+    /// elements of an array, reporting anything else as a diagnostic and treating it as empty
+    fn array<'a>(value: &'a Value, path: &str, diagnostics: &mut Vec<MarleaParserError>) -> &'a [Value] {
+        match value.as_array() {
+            Some(elements) => elements,
+            None => {
+                diagnostics.push(Self::invalid(path, "expected an array"));
+                &[]
+            }
+        }
+    }
+
+    /// path of a member of an object, keys which are not plain identifiers such as species names containing dots are quoted
+    fn member(path: &str, key: &str) -> String {
+        match !key.is_empty() && key.chars().all(|character| character.is_ascii_alphanumeric() || character == '_') {
+            true => format!("{}.{}", path, key),
+            false => format!("{}[{:?}]", path, key),
+        }
+    }
+
+    fn invalid(path: &str, reason: &str) -> MarleaParserError {
+        MarleaParserError::InvalidValue { file: None, value_path: path.to_string(), reason: reason.to_string() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{mem::discriminant, path::Path};
+
+    use marlea_engine::trial::reaction_network::solution::{Name, Count};
+
+    use crate::{CSVparser, MarleaParser, MarleaParserError, document::{DocumentFormat, DocumentParser}};
+
+    #[test]
+    fn document_parser_reads_every_format_alike() {
+        let expected = MarleaParser::parse(&Path::new("test_data").join("json").join("network.json")).unwrap();
+
+        for file in ["network.toml", "network.yaml"] {
+            let parsed = MarleaParser::parse(&Path::new("test_data").join("json").join(file)).unwrap();
+            assert_eq!(parsed.get_reactions(), expected.get_reactions(), "{}", file);
+            assert_eq!(parsed.get_solution().species_counts, expected.get_solution().species_counts, "{}", file);
+        }
+    }
+
+    #[test]
+    fn document_parser_validates_formats_identically() {
+        let toml = "[species]\nA = -1\n\"setup.call\" = 1\n\"A B\" = 2\n\n[[reactions]]\nreactants = [{ name = \"A\", coefficient = 0 }]\nrate = 1\n";
+        let yaml = "species:\n  A: -1\n  setup.call: 1\n  A B: 2\nreactions:\n  - reactants: [{ name: A, coefficient: 0 }]\n    rate: 1\n";
+        let json = r#"{ "species": { "A": -1, "setup.call": 1, "A B": 2 }, "reactions": [{ "reactants": [{ "name": "A", "coefficient": 0 }], "rate": 1 }] }"#;
+
+        for (format, source) in [(DocumentFormat::Toml, toml), (DocumentFormat::Yaml, yaml), (DocumentFormat::Json, json)] {
+            let (network, diagnostics) = DocumentParser::as_reaction_network_recovering(format, source);
+            let paths = diagnostics.iter()
+                .map(|diagnostic| match diagnostic {
+                    MarleaParserError::InvalidValue { value_path, .. } => value_path.as_str(),
+                    MarleaParserError::BadCoefficient { text, .. } | MarleaParserError::BadSpeciesCount { text, .. } => text.split(" = ").next().unwrap_or_default(),
+                    other => panic!("unexpected diagnostic {}", other),
+                })
+                .collect::<Vec<&str>>();
+            assert_eq!(paths, ["$.reactions[0].reactants[0].coefficient", "$.species.A", "$.species[\"A B\"]"], "{:?}", format);
+            assert_eq!(network.get_solution().species_counts.get(&Name(String::from("setup.call"))), Some(&Count(1)));
+        }
+    }
+
+    #[test]
+    fn document_parser_rejects_numbers_as_csv_does() {
+        let reaction = |coefficient: &str, rate: &str| format!(r#"{{ "reactions": [{{ "reactants": [{{ "name": "A", "coefficient": {} }}], "rate": {} }}] }}"#, coefficient, rate);
+        let species = |count: &str| format!(r#"{{ "species": {{ "A": {} }} }}"#, count);
+        let cases = [
+            ("A => NULL,0,\n", reaction("1", "0")),
+            ("A => NULL,2.5,\n", reaction("1", "2.5")),
+            ("A => NULL,1e-100,\n", reaction("1", "1e-100")),
+            ("A => NULL,1e30,\n", reaction("1", "1e30")),
+            ("(0) A => NULL,1,\n", reaction("0", "1")),
+            ("(1.5) A => NULL,1,\n", reaction("1.5", "1")),
+            ("A,2.5,\n", species("2.5")),
+            ("A,-1,\n", species("-1")),
+        ];
+
+        for (csv, json) in cases {
+            let expected = CSVparser::as_reaction_network(csv).unwrap_err();
+            let found = DocumentParser::as_reaction_network(DocumentFormat::Json, &json).unwrap_err();
+            assert_eq!(discriminant(&found), discriminant(&expected), "{} was {} in csv but {} in json", csv, expected, found);
+        }
+
+        // whole floats are exact however large, as they are in csv
+        let csv = CSVparser::as_reaction_network("A,1e19,\n").unwrap();
+        let toml = DocumentParser::as_reaction_network(DocumentFormat::Toml, "[species]\nA = 1e19\n").unwrap();
+        assert_eq!(toml.get_solution().species_counts, csv.get_solution().species_counts);
+    }
+
+    #[test]
+    fn document_parser_reports_syntax_location() {
+        match DocumentParser::as_reaction_network(DocumentFormat::Toml, "[species]\nA = \n").unwrap_err() {
+            MarleaParserError::Toml { location, .. } => assert_eq!(location.line, 2),
+            other => panic!("expected a toml error, found {}", other),
+        }
+        match DocumentParser::as_reaction_network(DocumentFormat::Yaml, "species:\n  A: [5\n").unwrap_err() {
+            MarleaParserError::Yaml { location, .. } => assert!(location.line >= 2),
+            other => panic!("expected a yaml error, found {}", other),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Renders a reaction network as a Graphviz DOT graph.
+//!
+//! The graph is bipartite, species are ellipses and reactions are boxes labelled with their rate.
+//! Edges run from each reactant to its reaction and from the reaction to each product, labelled with the coefficient.
+//! Species and reactions are ordered the same way as [crate::writer::CSVwriter] so output is deterministic.
+//!
+//! ```text
+//! digraph reaction_network {
+//!     rankdir=LR;
+//!     "A" [shape=ellipse];
+//!     "B" [shape=ellipse];
+//!     "reaction 0" [label="10", shape=box];
+//!     "A" -> "reaction 0" [label="1"];
+//!     "reaction 0" -> "B" [label="2"];
+//! }
+//! ```
+//!
+//! Render with `dot -Tsvg network.dot -o network.svg`.
+
+use std::{collections::BTreeMap, io::{self, Write}};
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, reaction::Reaction};
+
+use crate::writer::CSVwriter;
+
+/// Serializes a [ReactionNetwork] into a Graphviz DOT graph
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DOTwriter {
+    cluster_prefixes: bool,
+    fast_rate: Option<u64>,
+}
+
+impl DOTwriter {
+    pub fn new() -> Self {
+        DOTwriter { cluster_prefixes: false, fast_rate: None }
+    }
+
+    /// group species sharing a dotted name prefix into a cluster,
+    /// ie `next_value`, `next_value.less_than.2.index.0` and `next_value.less_than.2.index.1` are drawn together
+    pub fn cluster_prefixes(mut self, cluster_prefixes: bool) -> Self {
+        self.cluster_prefixes = cluster_prefixes;
+        self
+    }
+
+    /// leave out reactions with a rate of at least `rate`, such as the fast cleanup reactions of a circuit,
+    /// their species are still drawn
+    pub fn hide_fast_reactions(mut self, rate: u64) -> Self {
+        self.fast_rate = Some(rate);
+        self
+    }
+
+    /// write the network as DOT text
+    pub fn as_dot(&self, reaction_network: &ReactionNetwork) -> String {
+        let mut species = reaction_network.get_solution().species_counts.keys()
+            .map(|name| name.0.as_str())
+            .collect::<Vec<&str>>();
+        species.sort_unstable();
+
+        let mut reactions = reaction_network.get_reactions().iter().collect::<Vec<&Reaction>>();
+        reactions.sort_by_cached_key(|reaction| CSVwriter::as_row(reaction));
+
+        let mut output = String::from("digraph reaction_network {\n    rankdir=LR;\n");
+
+        // species without a dotted sibling stay outside any cluster
+        let mut clusters: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for name in &species {
+            let prefix = match self.cluster_prefixes {
+                true => name.split('.').next().unwrap_or(name),
+                false => name,
+            };
+            clusters.entry(prefix).or_default().push(name);
+        }
+        for (prefix, names) in clusters {
+            match names.len() > 1 || names[0] != prefix {
+                true => {
+                    output.push_str(&format!("    subgraph {} {{\n", Self::quote(&format!("cluster_{}", prefix))));
+                    output.push_str(&format!("        label={};\n", Self::quote(prefix)));
+                    for name in names {
+                        output.push_str(&format!("        {} [shape=ellipse];\n", Self::quote(name)));
+                    }
+                    output.push_str("    }\n");
+                },
+                false => output.push_str(&format!("    {} [shape=ellipse];\n", Self::quote(prefix))),
+            }
+        }
+
+        // reactions are numbered by their position among every reaction so hiding some does not renumber the rest,
+        // species names can not contain spaces so these never collide with a species
+        for (position, reaction) in reactions.iter().enumerate() {
+            if self.fast_rate.is_some_and(|fast_rate| reaction.get_reaction_rate() >= fast_rate) {
+                continue;
+            }
+
+            let node = Self::quote(&format!("reaction {}", position));
+            output.push_str(&format!("    {} [label=\"{}\", shape=box];\n", node, reaction.get_reaction_rate()));
+            for term in reaction.get_reactants() {
+                output.push_str(&format!("    {} -> {} [label=\"{}\"];\n", Self::quote(&term.get_species_name().0), node, term.get_coefficient().0));
+            }
+            for term in reaction.get_products() {
+                output.push_str(&format!("    {} -> {} [label=\"{}\"];\n", node, Self::quote(&term.get_species_name().0), term.get_coefficient().0));
+            }
+        }
+
+        output.push_str("}\n");
+        output
+    }
+
+    /// write the network as DOT text to any writer such as a file
+    pub fn write(&self, reaction_network: &ReactionNetwork, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(self.as_dot(reaction_network).as_bytes())
+    }
+
+    /// a DOT identifier, names may contain any character other than csv delimiters so are always quoted
+    fn quote(text: &str) -> String {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{CSVparser, MarleaParser, dot::DOTwriter};
+
+    #[test]
+    fn dot_writer_draws_bipartite_graph() {
+        let network = CSVparser::as_reaction_network("A + 2 B => C,10,\nC => NULL,1,\nA,5,\n").unwrap();
+
+        assert_eq!(DOTwriter::new().as_dot(&network), concat!(
+            "digraph reaction_network {\n",
+            "    rankdir=LR;\n",
+            "    \"A\" [shape=ellipse];\n",
+            "    \"B\" [shape=ellipse];\n",
+            "    \"C\" [shape=ellipse];\n",
+            "    \"reaction 0\" [label=\"10\", shape=box];\n",
+            "    \"A\" -> \"reaction 0\" [label=\"1\"];\n",
+            "    \"B\" -> \"reaction 0\" [label=\"2\"];\n",
+            "    \"reaction 0\" -> \"C\" [label=\"1\"];\n",
+            "    \"reaction 1\" [label=\"1\", shape=box];\n",
+            "    \"C\" -> \"reaction 1\" [label=\"1\"];\n",
+            "}\n",
+        ));
+    }
+
+    #[test]
+    fn dot_writer_clusters_prefixes_and_hides_fast_reactions() {
+        let network = CSVparser::as_reaction_network("other + value.copy => value,100,\nvalue => NULL,1,\n").unwrap();
+
+        let dot = DOTwriter::new().cluster_prefixes(true).hide_fast_reactions(100).as_dot(&network);
+        assert_eq!(dot, concat!(
+            "digraph reaction_network {\n",
+            "    rankdir=LR;\n",
+            "    \"other\" [shape=ellipse];\n",
+            "    subgraph \"cluster_value\" {\n",
+            "        label=\"value\";\n",
+            "        \"value\" [shape=ellipse];\n",
+            "        \"value.copy\" [shape=ellipse];\n",
+            "    }\n",
+            "    \"reaction 1\" [label=\"1\", shape=box];\n",
+            "    \"value\" -> \"reaction 1\" [label=\"1\"];\n",
+            "}\n",
+        ));
+    }
+
+    #[test]
+    fn dot_writer_hides_fibonacci_cleanup() {
+        let parsed = MarleaParser::parse(&Path::new("test_data").join("Fibonacci_calculator.csv")).unwrap();
+
+        let slow = parsed.get_reactions().iter().filter(|reaction| reaction.get_reaction_rate() < 10000).count();
+        let dot = DOTwriter::new().cluster_prefixes(true).hide_fast_reactions(10000).as_dot(&parsed);
+        assert_eq!(dot.matches("shape=box").count(), slow);
+        assert!(dot.contains("subgraph \"cluster_next_value\""));
+    }
+}
@@ -0,0 +1,583 @@
+//! Error types returned by the marlea parser
+
+use std::{fmt, io, path::{Path, PathBuf}, str::Utf8Error};
+
+use pest::{RuleType, error::{Error as PestError, ErrorVariant, InputLocation, LineColLocation}, iterators::Pair};
+
+use crate::{Rule, antimony};
+
+/// Location of a piece of source text which caused an error.
+/// line and column are 1 based as reported by pest, start and end are byte offsets into the source.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SourceSpan {
+    pub path: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl SourceSpan {
+    /// location of the text matched by a token
+    pub fn from_pair<R: RuleType>(token: &Pair<'_, R>) -> Self {
+        let (line, column) = token.line_col();
+        let span = token.as_span();
+        SourceSpan { path: None, line, column, start: span.start(), end: span.end() }
+    }
+
+    /// location pest reported for a failed parse
+    pub fn from_pest_error<R: RuleType>(error: &PestError<R>) -> Self {
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(line_col) => line_col,
+            LineColLocation::Span(start, _) => start,
+        };
+        let (start, end) = match error.location {
+            InputLocation::Pos(pos) => (pos, pos),
+            InputLocation::Span(span) => span,
+        };
+        SourceSpan { path: None, line, column, start, end }
+    }
+
+    /// location of a single byte offset within source, used for errors which are not tied to a token
+    pub fn from_offset(source: &str, offset: usize) -> Self {
+        let preceding = &source[..offset];
+        let line = preceding.matches('\n').count() + 1;
+        let column = match preceding.rfind('\n') {
+            Some(line_start) => preceding[line_start + 1..].chars().count() + 1,
+            None => preceding.chars().count() + 1,
+        };
+        SourceSpan { path: None, line, column, start: offset, end: offset }
+    }
+
+    /// location of a 1 based line and column reported by a parser which does not track byte offsets
+    pub fn from_line_column(source: &str, line: usize, column: usize) -> Self {
+        let line_start = source.split_inclusive('\n').take(line.saturating_sub(1)).map(str::len).sum::<usize>();
+        let offset = source[line_start..].char_indices().nth(column.saturating_sub(1)).map_or(source.len(), |(index, _)| line_start + index);
+        SourceSpan::from_offset(source, offset)
+    }
+}
+
+impl fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}:{}:{}", path.display(), self.line, self.column),
+            None => write!(f, "line {} column {}", self.line, self.column),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MarleaParserError {
+    /// source text did not match the grammar
+    Syntax { location: SourceSpan, source: Box<PestError<Rule>> },
+    /// a reaction token contained no reaction rate
+    MissingRate { location: SourceSpan },
+    /// a term was missing its species name or coefficient
+    BadTerm { location: SourceSpan, text: String },
+    /// a coefficient could not be converted into a count
+    BadCoefficient { location: SourceSpan, text: String },
+    /// a reaction rate is too large to be converted into a rate
+    BadRate { location: SourceSpan, text: String },
+    /// a reaction rate has a fractional part which the engine's integer rates cannot represent
+    InexactRate { location: SourceSpan, text: String },
+    /// a reaction rate is zero so the reaction could never occur
+    ZeroRate { location: SourceSpan, text: String },
+    /// a species count was missing its name or count, or the count was negative or too large
+    BadSpeciesCount { location: SourceSpan, text: String },
+    /// a count or coefficient evaluated to a value with a fractional part
+    NonIntegral { location: SourceSpan, text: String },
+    /// an expression divided by zero
+    DivisionByZero { location: SourceSpan },
+    /// an intermediate value of an expression was too large to represent exactly
+    ArithmeticOverflow { location: SourceSpan },
+    /// an expression raised a value to a fractional or enormous power
+    BadExponent { location: SourceSpan },
+    /// a parameter was used which was never declared
+    UndefinedParameter { location: SourceSpan, name: String },
+    /// a parameter was declared more than once
+    DuplicateParameter { location: SourceSpan, name: String, previous: Box<SourceSpan> },
+    /// a parameter row was missing its name or value
+    BadParameter { location: SourceSpan, text: String },
+    /// a use row named a template which is not defined above it
+    UndefinedTemplate { location: SourceSpan, name: String },
+    /// a template was defined more than once
+    DuplicateTemplate { location: SourceSpan, name: String, previous: Box<SourceSpan> },
+    /// a reaction was written more than once
+    DuplicateReaction { location: SourceSpan, previous: Box<SourceSpan> },
+    /// a reaction was written again with the same reactants and products but a different rate
+    ConflictingRate { location: SourceSpan, rate: u64, previous_rate: u64, previous: Box<SourceSpan> },
+    /// the initial count of a species was set by more than one row
+    DuplicateSpeciesCount { location: SourceSpan, name: String, previous: Box<SourceSpan> },
+    /// a species is consumed by reactions but is never produced and starts with a count of zero
+    DeadSpecies { location: SourceSpan, name: String },
+    /// a species is produced by reactions but is never consumed or observed
+    UnusedSpecies { location: SourceSpan, name: String },
+    /// a species name is a small edit away from a more common name and is likely a typo
+    NearMissName { location: SourceSpan, name: String, suggestion: String },
+    /// a use row gave a different number of arguments than the template has parameters
+    TemplateArguments { location: SourceSpan, name: String, expected: usize, found: usize },
+    /// an included file could not be found
+    MissingInclude { location: SourceSpan, path: PathBuf, source: io::Error },
+    /// a file includes itself directly or through other included files
+    IncludeCycle { location: SourceSpan, path: PathBuf },
+    /// an include row was parsed from text which has no file to resolve it against
+    UnresolvedInclude { location: SourceSpan, path: String },
+    /// an sbml source was not well formed xml
+    Xml { location: SourceSpan, source: Box<roxmltree::Error> },
+    /// an sbml document is missing something it requires, such as the id of a species
+    InvalidSbml { location: SourceSpan, reason: String },
+    /// an sbml document uses a construct which has no equivalent in a reaction network
+    UnsupportedSbml { location: SourceSpan, construct: &'static str },
+    /// the kinetic law of an sbml reaction is missing or is not mass action over the reaction's reactants
+    NotMassAction { location: SourceSpan, reaction: String },
+    /// an antimony source did not match the antimony grammar
+    Antimony { location: SourceSpan, source: Box<PestError<antimony::Rule>> },
+    /// an antimony model refers to something it never defines, such as a rate constant with no value
+    InvalidAntimony { location: SourceSpan, reason: String },
+    /// an antimony model uses a construct which has no equivalent in a reaction network
+    UnsupportedAntimony { location: SourceSpan, construct: &'static str },
+    /// a BioNetGen network file has a line which does not fit the block it is in
+    InvalidBionetgen { location: SourceSpan, reason: String },
+    /// a BioNetGen network uses a construct which has no equivalent in a reaction network
+    UnsupportedBionetgen { location: SourceSpan, construct: &'static str },
+    /// a json source was not well formed json
+    Json { location: SourceSpan, source: Box<serde_json::Error> },
+    /// a toml source was not well formed toml
+    Toml { location: SourceSpan, source: Box<toml::de::Error> },
+    /// a yaml source was not well formed yaml
+    Yaml { location: SourceSpan, source: Box<serde_norway::Error> },
+    /// a value in a json, toml or yaml document does not match the network schema, value_path is where it is such as `$.reactions[2].rate`
+    InvalidValue { file: Option<PathBuf>, value_path: String, reason: String },
+    /// the token stream contained a token where a different one was expected
+    UnexpectedToken { location: SourceSpan, found: &'static str, expected: &'static str },
+    /// the source was accepted by the grammar but produced no tokens
+    EmptyTokenStream { path: Option<PathBuf> },
+    /// the source file could not be opened or read
+    Io { path: PathBuf, source: io::Error },
+    /// the source file was not valid utf-8
+    Decode { location: SourceSpan, source: Utf8Error },
+    /// the source file has an extension no parser exists for
+    UnsupportedExt { path: PathBuf },
+    /// the source path has no extension
+    InvalidFile { path: PathBuf },
+}
+
+impl MarleaParserError {
+    /// location in the source which caused this error if there is one
+    pub fn location(&self) -> Option<&SourceSpan> {
+        match self {
+            MarleaParserError::Syntax { location, .. }
+            | MarleaParserError::MissingRate { location }
+            | MarleaParserError::BadTerm { location, .. }
+            | MarleaParserError::BadCoefficient { location, .. }
+            | MarleaParserError::BadRate { location, .. }
+            | MarleaParserError::InexactRate { location, .. }
+            | MarleaParserError::ZeroRate { location, .. }
+            | MarleaParserError::BadSpeciesCount { location, .. }
+            | MarleaParserError::NonIntegral { location, .. }
+            | MarleaParserError::DivisionByZero { location }
+            | MarleaParserError::ArithmeticOverflow { location }
+            | MarleaParserError::BadExponent { location }
+            | MarleaParserError::UndefinedParameter { location, .. }
+            | MarleaParserError::DuplicateParameter { location, .. }
+            | MarleaParserError::BadParameter { location, .. }
+            | MarleaParserError::UndefinedTemplate { location, .. }
+            | MarleaParserError::DeadSpecies { location, .. }
+            | MarleaParserError::UnusedSpecies { location, .. }
+            | MarleaParserError::NearMissName { location, .. }
+            | MarleaParserError::DuplicateTemplate { location, .. }
+            | MarleaParserError::DuplicateReaction { location, .. }
+            | MarleaParserError::ConflictingRate { location, .. }
+            | MarleaParserError::DuplicateSpeciesCount { location, .. }
+            | MarleaParserError::TemplateArguments { location, .. }
+            | MarleaParserError::MissingInclude { location, .. }
+            | MarleaParserError::IncludeCycle { location, .. }
+            | MarleaParserError::UnresolvedInclude { location, .. }
+            | MarleaParserError::Xml { location, .. }
+            | MarleaParserError::InvalidSbml { location, .. }
+            | MarleaParserError::UnsupportedSbml { location, .. }
+            | MarleaParserError::NotMassAction { location, .. }
+            | MarleaParserError::Antimony { location, .. }
+            | MarleaParserError::InvalidAntimony { location, .. }
+            | MarleaParserError::UnsupportedAntimony { location, .. }
+            | MarleaParserError::InvalidBionetgen { location, .. }
+            | MarleaParserError::UnsupportedBionetgen { location, .. }
+            | MarleaParserError::Json { location, .. }
+            | MarleaParserError::Toml { location, .. }
+            | MarleaParserError::Yaml { location, .. }
+            | MarleaParserError::UnexpectedToken { location, .. }
+            | MarleaParserError::Decode { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    // mutable location used to move errors found in a fragment of source
+    fn location_mut(&mut self) -> Option<&mut SourceSpan> {
+        match self {
+            MarleaParserError::Syntax { location, .. }
+            | MarleaParserError::MissingRate { location }
+            | MarleaParserError::BadTerm { location, .. }
+            | MarleaParserError::BadCoefficient { location, .. }
+            | MarleaParserError::BadRate { location, .. }
+            | MarleaParserError::InexactRate { location, .. }
+            | MarleaParserError::ZeroRate { location, .. }
+            | MarleaParserError::BadSpeciesCount { location, .. }
+            | MarleaParserError::NonIntegral { location, .. }
+            | MarleaParserError::DivisionByZero { location }
+            | MarleaParserError::ArithmeticOverflow { location }
+            | MarleaParserError::BadExponent { location }
+            | MarleaParserError::UndefinedParameter { location, .. }
+            | MarleaParserError::DuplicateParameter { location, .. }
+            | MarleaParserError::BadParameter { location, .. }
+            | MarleaParserError::UndefinedTemplate { location, .. }
+            | MarleaParserError::DeadSpecies { location, .. }
+            | MarleaParserError::UnusedSpecies { location, .. }
+            | MarleaParserError::NearMissName { location, .. }
+            | MarleaParserError::DuplicateTemplate { location, .. }
+            | MarleaParserError::DuplicateReaction { location, .. }
+            | MarleaParserError::ConflictingRate { location, .. }
+            | MarleaParserError::DuplicateSpeciesCount { location, .. }
+            | MarleaParserError::TemplateArguments { location, .. }
+            | MarleaParserError::MissingInclude { location, .. }
+            | MarleaParserError::IncludeCycle { location, .. }
+            | MarleaParserError::UnresolvedInclude { location, .. }
+            | MarleaParserError::Xml { location, .. }
+            | MarleaParserError::InvalidSbml { location, .. }
+            | MarleaParserError::UnsupportedSbml { location, .. }
+            | MarleaParserError::NotMassAction { location, .. }
+            | MarleaParserError::Antimony { location, .. }
+            | MarleaParserError::InvalidAntimony { location, .. }
+            | MarleaParserError::UnsupportedAntimony { location, .. }
+            | MarleaParserError::InvalidBionetgen { location, .. }
+            | MarleaParserError::UnsupportedBionetgen { location, .. }
+            | MarleaParserError::Json { location, .. }
+            | MarleaParserError::Toml { location, .. }
+            | MarleaParserError::Yaml { location, .. }
+            | MarleaParserError::UnexpectedToken { location, .. }
+            | MarleaParserError::Decode { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// path of the file this error came from if it is known
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            MarleaParserError::EmptyTokenStream { path } => path.as_deref(),
+            MarleaParserError::InvalidValue { file, .. } => file.as_deref(),
+            MarleaParserError::Io { path, .. }
+            | MarleaParserError::UnsupportedExt { path }
+            | MarleaParserError::InvalidFile { path } => Some(path),
+            _ => self.location().and_then(|location| location.path.as_deref()),
+        }
+    }
+
+    /// short description of the error without its location, used as the headline of a rendered diagnostic
+    pub fn summary(&self) -> String {
+        match self {
+            MarleaParserError::Syntax { .. } => String::from("could not parse row"),
+            MarleaParserError::MissingRate { .. } => String::from("missing reaction rate"),
+            MarleaParserError::BadTerm { .. } => String::from("malformed term"),
+            MarleaParserError::BadCoefficient { .. } => String::from("invalid coefficient"),
+            MarleaParserError::BadRate { .. } => String::from("invalid reaction rate"),
+            MarleaParserError::InexactRate { .. } => String::from("reaction rate is not a whole number"),
+            MarleaParserError::ZeroRate { .. } => String::from("reaction rate is zero"),
+            MarleaParserError::BadSpeciesCount { .. } => String::from("malformed species count"),
+            MarleaParserError::NonIntegral { .. } => String::from("count is not a whole number"),
+            MarleaParserError::DivisionByZero { .. } => String::from("division by zero"),
+            MarleaParserError::ArithmeticOverflow { .. } => String::from("arithmetic overflow"),
+            MarleaParserError::BadExponent { .. } => String::from("invalid exponent"),
+            MarleaParserError::UndefinedParameter { name, .. } => format!("undefined parameter `{}`", name),
+            MarleaParserError::DuplicateParameter { name, .. } => format!("parameter `{}` is declared more than once", name),
+            MarleaParserError::BadParameter { .. } => String::from("invalid parameter value"),
+            MarleaParserError::UndefinedTemplate { name, .. } => format!("undefined template `{}`", name),
+            MarleaParserError::DuplicateTemplate { name, .. } => format!("template `{}` is defined more than once", name),
+            MarleaParserError::DuplicateReaction { .. } => String::from("reaction is written more than once"),
+            MarleaParserError::ConflictingRate { .. } => String::from("reaction is written again with a different rate"),
+            MarleaParserError::DuplicateSpeciesCount { name, .. } => format!("initial count of `{}` is set more than once", name),
+            MarleaParserError::DeadSpecies { name, .. } => format!("species `{}` is consumed but never produced and starts at zero", name),
+            MarleaParserError::UnusedSpecies { name, .. } => format!("species `{}` is produced but never consumed", name),
+            MarleaParserError::NearMissName { name, suggestion, .. } => format!("species `{}` is named like `{}`", name, suggestion),
+            MarleaParserError::TemplateArguments { name, expected, .. } => format!("template `{}` takes {} arguments", name, expected),
+            MarleaParserError::MissingInclude { path, .. } => format!("included file {} could not be read", path.display()),
+            MarleaParserError::IncludeCycle { path, .. } => format!("{} includes itself", path.display()),
+            MarleaParserError::UnresolvedInclude { path, .. } => format!("cannot resolve include \"{}\" without a source file", path),
+            MarleaParserError::Xml { .. } => String::from("could not parse xml"),
+            MarleaParserError::InvalidSbml { .. } => String::from("invalid sbml"),
+            MarleaParserError::UnsupportedSbml { construct, .. } => format!("unsupported sbml {}", construct),
+            MarleaParserError::NotMassAction { reaction, .. } => format!("reaction `{}` does not have a mass action kinetic law", reaction),
+            MarleaParserError::Antimony { .. } => String::from("could not parse antimony"),
+            MarleaParserError::InvalidAntimony { .. } => String::from("invalid antimony"),
+            MarleaParserError::UnsupportedAntimony { construct, .. } => format!("unsupported antimony {}", construct),
+            MarleaParserError::InvalidBionetgen { .. } => String::from("invalid BioNetGen network"),
+            MarleaParserError::UnsupportedBionetgen { construct, .. } => format!("unsupported BioNetGen {}", construct),
+            MarleaParserError::Json { .. } => String::from("could not parse json"),
+            MarleaParserError::Toml { .. } => String::from("could not parse toml"),
+            MarleaParserError::Yaml { .. } => String::from("could not parse yaml"),
+            MarleaParserError::InvalidValue { value_path, .. } => format!("invalid value at `{}`", value_path),
+            MarleaParserError::UnexpectedToken { found, expected, .. } => format!("found unexpected {} token, expected {} token", found, expected),
+            MarleaParserError::Decode { .. } => String::from("source is not valid utf-8"),
+            _ => self.to_string(),
+        }
+    }
+
+    /// text to print beneath the offending source text
+    pub fn label(&self) -> String {
+        match self {
+            MarleaParserError::Syntax { source, .. } => source.variant.message().to_string(),
+            MarleaParserError::MissingRate { .. } => String::from("reaction has no rate"),
+            MarleaParserError::BadTerm { .. } => String::from("term is missing a species name or coefficient"),
+            MarleaParserError::BadCoefficient { .. } => String::from("coefficient is not a positive count"),
+            MarleaParserError::BadRate { .. } => String::from("rate does not fit in a reaction rate"),
+            MarleaParserError::InexactRate { .. } => String::from("engine reaction rates are integers"),
+            MarleaParserError::ZeroRate { .. } => String::from("reaction can never occur"),
+            MarleaParserError::BadSpeciesCount { .. } => String::from("species count is missing, negative or too large"),
+            MarleaParserError::NonIntegral { .. } => String::from("evaluates to a fraction"),
+            MarleaParserError::DivisionByZero { .. } => String::from("right hand side evaluates to zero"),
+            MarleaParserError::ArithmeticOverflow { .. } => String::from("result is too large to represent exactly"),
+            MarleaParserError::BadExponent { .. } => String::from("exponent must be a whole number"),
+            MarleaParserError::UndefinedParameter { .. } => String::from("not declared by any parameter row"),
+            MarleaParserError::DuplicateParameter { previous, .. } => format!("previously declared at {}", previous),
+            MarleaParserError::BadParameter { .. } => String::from("expected a name and a value"),
+            MarleaParserError::UndefinedTemplate { .. } => String::from("not defined above this row"),
+            MarleaParserError::DuplicateTemplate { previous, .. } => format!("previously defined at {}", previous),
+            MarleaParserError::DuplicateReaction { previous, .. } => format!("previously written at {}", previous),
+            MarleaParserError::ConflictingRate { rate, previous_rate, previous, .. } => format!("rate {} here but {} at {}", rate, previous_rate, previous),
+            MarleaParserError::DuplicateSpeciesCount { previous, .. } => format!("previously set at {}", previous),
+            MarleaParserError::DeadSpecies { .. } => String::from("first consumed here"),
+            MarleaParserError::UnusedSpecies { .. } => String::from("first produced here"),
+            MarleaParserError::NearMissName { .. } => String::from("first used here"),
+            MarleaParserError::TemplateArguments { found, .. } => format!("{} arguments given", found),
+            MarleaParserError::MissingInclude { source, .. } => source.to_string(),
+            MarleaParserError::IncludeCycle { .. } => String::from("included again here"),
+            MarleaParserError::UnresolvedInclude { .. } => String::from("no file to resolve this path against"),
+            MarleaParserError::Xml { source, .. } => source.to_string(),
+            MarleaParserError::InvalidSbml { reason, .. } => reason.clone(),
+            MarleaParserError::UnsupportedSbml { .. } => String::from("no equivalent in a reaction network"),
+            MarleaParserError::NotMassAction { .. } => String::from("expected a rate constant multiplied by each reactant"),
+            MarleaParserError::Antimony { source, .. } => source.variant.message().to_string(),
+            MarleaParserError::InvalidAntimony { reason, .. } => reason.clone(),
+            MarleaParserError::UnsupportedAntimony { .. } => String::from("no equivalent in a reaction network"),
+            MarleaParserError::InvalidBionetgen { reason, .. } => reason.clone(),
+            MarleaParserError::UnsupportedBionetgen { .. } => String::from("no equivalent in a reaction network"),
+            MarleaParserError::Json { source, .. } => source.to_string(),
+            MarleaParserError::Toml { source, .. } => source.message().to_string(),
+            MarleaParserError::Yaml { source, .. } => source.to_string(),
+            MarleaParserError::InvalidValue { reason, .. } => reason.clone(),
+            MarleaParserError::UnexpectedToken { expected, .. } => format!("expected {}", expected),
+            MarleaParserError::Decode { .. } => String::from("invalid utf-8 byte sequence"),
+            _ => String::new(),
+        }
+    }
+
+    /// hint on how to fix the error if there is a useful one
+    pub fn help(&self) -> Option<String> {
+        match self {
+            MarleaParserError::Syntax { source, .. } => match &source.variant {
+                ErrorVariant::ParsingError { positives, .. } if positives.contains(&Rule::reaction_rate) || positives.contains(&Rule::expression) => Some(String::from("rates and counts are numbers, parameters or expressions such as 10, 2.5e3, fast or fast * 10")),
+                ErrorVariant::ParsingError { positives, .. } if positives.contains(&Rule::coefficient) => Some(String::from("coefficients are whole numbers, parameters or bracketed expressions such as 2, n or (n + 1), separated from the species name by a space")),
+                ErrorVariant::ParsingError { positives, .. } if positives.contains(&Rule::term) || positives.contains(&Rule::name) => Some(String::from("each side of a reaction is NULL or species separated by `+`")),
+                _ => Some(String::from("rows are either `reactants => products, rate,`, `reactants <=> products, forward rate, reverse rate,` or `species, count,`")),
+            },
+            MarleaParserError::MissingRate { .. } => Some(String::from("reactions need a rate and reversible reactions a forward and a reverse rate, each a number, parameter or expression such as 10, 2.5e3 or fast * 10")),
+            MarleaParserError::BadCoefficient { .. } => Some(String::from("coefficients must evaluate to a whole number from 1 to 18446744073709551615")),
+            MarleaParserError::BadSpeciesCount { .. } => Some(String::from("species rows are `name, count,` where the count evaluates to a whole number from 0 to 18446744073709551615")),
+            MarleaParserError::BadRate { .. } => Some(String::from("reaction rates must evaluate to a whole number from 1 to 18446744073709551615")),
+            MarleaParserError::InexactRate { .. } => Some(String::from("scale the network's rates so that every rate is a whole number")),
+            MarleaParserError::ZeroRate { .. } => Some(String::from("remove the reaction or give it a positive rate")),
+            MarleaParserError::NonIntegral { .. } => Some(String::from("counts and coefficients must evaluate to whole numbers")),
+            MarleaParserError::ArithmeticOverflow { .. } => Some(String::from("expressions are evaluated exactly and every intermediate value must fit in 128 bits")),
+            MarleaParserError::BadExponent { .. } => Some(String::from("exponents must be whole numbers no larger than 4294967295")),
+            MarleaParserError::UndefinedParameter { name, .. } => Some(format!("declare it with a row such as `#param {} = 10`, parameters used by another parameter must be declared above it", name)),
+            MarleaParserError::DuplicateParameter { .. } => Some(String::from("rename or remove one of the declarations")),
+            MarleaParserError::BadParameter { .. } => Some(String::from("parameters are declared as `#param name = value`")),
+            MarleaParserError::UndefinedTemplate { name, .. } => Some(format!("define it above with a `#template {}(X)` block ending in an `#end` row", name)),
+            MarleaParserError::DuplicateTemplate { .. } => Some(String::from("rename or remove one of the definitions")),
+            MarleaParserError::DuplicateReaction { .. } => Some(String::from("a network holds each reaction once, remove one of the rows")),
+            MarleaParserError::ConflictingRate { .. } => Some(String::from("both reactions occur independently, remove one or combine them into a single row with the intended rate")),
+            MarleaParserError::DuplicateSpeciesCount { .. } => Some(String::from("only the last count is used, remove the others")),
+            MarleaParserError::DeadSpecies { .. } => Some(String::from("reactions consuming it can never occur, give it an initial count or a reaction which produces it")),
+            MarleaParserError::UnusedSpecies { .. } => Some(String::from("mark it as observed if it is an output of the network, otherwise remove it from the products")),
+            MarleaParserError::NearMissName { suggestion, .. } => Some(format!("did you mean `{}`?", suggestion)),
+            MarleaParserError::TemplateArguments { .. } => Some(String::from("give one species name for each template parameter")),
+            MarleaParserError::MissingInclude { .. } => Some(String::from("include paths are relative to the directory of the including file")),
+            MarleaParserError::IncludeCycle { .. } => Some(String::from("remove one of the include rows which form the cycle")),
+            MarleaParserError::UnresolvedInclude { .. } => Some(String::from("parse the network from a file with MarleaParser so includes can be found")),
+            MarleaParserError::UnsupportedSbml { .. } => Some(String::from("only species with an initial amount, parameters, compartments and reactions with mass action kinetic laws can be imported")),
+            MarleaParserError::NotMassAction { .. } => Some(String::from("write the law as `k * A * B` over the reactants, or `kf * A * B - kr * C` for a reversible reaction")),
+            MarleaParserError::Antimony { .. } => Some(String::from("statements are reactions such as `J1: A + B -> C; k1*A*B`, species declarations or assignments such as `k1 = 10`")),
+            MarleaParserError::UnsupportedAntimony { .. } => Some(String::from("only species, compartments, rate constants and reactions with mass action rate laws can be imported")),
+            MarleaParserError::InvalidBionetgen { .. } => Some(String::from("lines are `index name value` for parameters, `index species count` for species and `index reactants products rate` for reactions")),
+            MarleaParserError::UnsupportedBionetgen { .. } => Some(String::from("only parameters, species and reactions with mass action rate constants can be imported")),
+            MarleaParserError::InvalidValue { .. } => Some(String::from("networks are objects with a `species` array of {name, count} objects and a `reactions` array of {reactants, products, rate} objects")),
+            MarleaParserError::Decode { .. } => Some(String::from("save the file with utf-8 encoding")),
+            MarleaParserError::UnsupportedExt { .. } => Some(String::from("supported formats are: csv, xml, sbml, ant, net, json, toml, yaml, yml")),
+            _ => None,
+        }
+    }
+
+    /// move the location of an error found while parsing a fragment of source, such as a single field of a line,
+    /// to where that fragment starts within the whole source
+    pub(crate) fn offset_by(mut self, source: &str, offset: usize) -> Self {
+        if let Some(location) = self.location_mut() {
+            let mut moved = SourceSpan::from_offset(source, offset + location.start);
+            moved.end = offset + location.end;
+            moved.path = location.path.take();
+            *location = moved;
+        }
+        self
+    }
+
+    /// a value which is not a whole number is an inexact rate when it is evaluated as a reaction rate
+    pub(crate) fn into_rate_error(self) -> Self {
+        match self {
+            MarleaParserError::NonIntegral { location, text } => MarleaParserError::InexactRate { location, text },
+            other => other,
+        }
+    }
+
+    /// move the location of an error found in text which has no position of its own, such as a number decoded from a document
+    pub(crate) fn relocate(mut self, location: SourceSpan) -> Self {
+        if let Some(current) = self.location_mut() {
+            *current = location;
+        }
+        self
+    }
+
+    /// attach the path of the file which was being parsed when this error occurred
+    pub fn with_path(mut self, file: &Path) -> Self {
+        match &mut self {
+            MarleaParserError::Syntax { location, .. }
+            | MarleaParserError::MissingRate { location }
+            | MarleaParserError::BadTerm { location, .. }
+            | MarleaParserError::BadCoefficient { location, .. }
+            | MarleaParserError::BadRate { location, .. }
+            | MarleaParserError::InexactRate { location, .. }
+            | MarleaParserError::ZeroRate { location, .. }
+            | MarleaParserError::BadSpeciesCount { location, .. }
+            | MarleaParserError::NonIntegral { location, .. }
+            | MarleaParserError::DivisionByZero { location }
+            | MarleaParserError::ArithmeticOverflow { location }
+            | MarleaParserError::BadExponent { location }
+            | MarleaParserError::UndefinedParameter { location, .. }
+            | MarleaParserError::BadParameter { location, .. }
+            | MarleaParserError::UndefinedTemplate { location, .. }
+            | MarleaParserError::DeadSpecies { location, .. }
+            | MarleaParserError::UnusedSpecies { location, .. }
+            | MarleaParserError::NearMissName { location, .. }
+            | MarleaParserError::TemplateArguments { location, .. }
+            | MarleaParserError::MissingInclude { location, .. }
+            | MarleaParserError::IncludeCycle { location, .. }
+            | MarleaParserError::UnresolvedInclude { location, .. }
+            | MarleaParserError::Xml { location, .. }
+            | MarleaParserError::InvalidSbml { location, .. }
+            | MarleaParserError::UnsupportedSbml { location, .. }
+            | MarleaParserError::NotMassAction { location, .. }
+            | MarleaParserError::Antimony { location, .. }
+            | MarleaParserError::InvalidAntimony { location, .. }
+            | MarleaParserError::UnsupportedAntimony { location, .. }
+            | MarleaParserError::InvalidBionetgen { location, .. }
+            | MarleaParserError::UnsupportedBionetgen { location, .. }
+            | MarleaParserError::Json { location, .. }
+            | MarleaParserError::Toml { location, .. }
+            | MarleaParserError::Yaml { location, .. }
+            | MarleaParserError::UnexpectedToken { location, .. }
+            | MarleaParserError::Decode { location, .. } => {
+                location.path.get_or_insert_with(|| file.to_path_buf());
+            },
+            MarleaParserError::InvalidValue { file: value_file, .. } => {
+                value_file.get_or_insert_with(|| file.to_path_buf());
+            },
+            MarleaParserError::DuplicateParameter { location, previous, .. }
+            | MarleaParserError::DuplicateTemplate { location, previous, .. }
+            | MarleaParserError::DuplicateReaction { location, previous }
+            | MarleaParserError::ConflictingRate { location, previous, .. }
+            | MarleaParserError::DuplicateSpeciesCount { location, previous, .. } => {
+                location.path.get_or_insert_with(|| file.to_path_buf());
+                previous.path.get_or_insert_with(|| file.to_path_buf());
+            },
+            MarleaParserError::EmptyTokenStream { path } => {
+                path.get_or_insert_with(|| file.to_path_buf());
+            },
+            _ => (),
+        }
+        self
+    }
+}
+
+impl fmt::Display for MarleaParserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarleaParserError::Syntax { location, source } => write!(f, "syntax error at {}\n{}", location, source),
+            MarleaParserError::MissingRate { location } => write!(f, "missing reaction rate at {}", location),
+            MarleaParserError::BadTerm { location, text } => write!(f, "malformed term {} at {}", text, location),
+            MarleaParserError::BadCoefficient { location, text } => write!(f, "coefficient {} at {} is not a valid count", text, location),
+            MarleaParserError::BadRate { location, text } => write!(f, "reaction rate {} at {} is not a valid rate", text, location),
+            MarleaParserError::InexactRate { location, text } => write!(f, "reaction rate {} at {} cannot be represented exactly as an integer rate", text, location),
+            MarleaParserError::ZeroRate { location, text } => write!(f, "reaction rate {} at {} is zero", text, location),
+            MarleaParserError::BadSpeciesCount { location, text } => write!(f, "malformed species count {} at {}", text, location),
+            MarleaParserError::NonIntegral { location, text } => write!(f, "{} at {} does not evaluate to a whole number", text, location),
+            MarleaParserError::DivisionByZero { location } => write!(f, "division by zero at {}", location),
+            MarleaParserError::ArithmeticOverflow { location } => write!(f, "arithmetic overflow at {}", location),
+            MarleaParserError::BadExponent { location } => write!(f, "invalid exponent at {}", location),
+            MarleaParserError::UndefinedParameter { location, name } => write!(f, "undefined parameter {} at {}", name, location),
+            MarleaParserError::DuplicateParameter { location, name, previous } => write!(f, "parameter {} at {} was already declared at {}", name, location, previous),
+            MarleaParserError::BadParameter { location, text } => write!(f, "malformed parameter {} at {}", text, location),
+            MarleaParserError::UndefinedTemplate { location, name } => write!(f, "undefined template {} at {}", name, location),
+            MarleaParserError::DuplicateTemplate { location, name, previous } => write!(f, "template {} at {} was already defined at {}", name, location, previous),
+            MarleaParserError::DuplicateReaction { location, previous } => write!(f, "reaction at {} was already written at {}", location, previous),
+            MarleaParserError::ConflictingRate { location, rate, previous_rate, previous } => write!(f, "reaction at {} has rate {} but was already written with rate {} at {}", location, rate, previous_rate, previous),
+            MarleaParserError::DuplicateSpeciesCount { location, name, previous } => write!(f, "initial count of {} at {} was already set at {}", name, location, previous),
+            MarleaParserError::DeadSpecies { location, name } => write!(f, "species {} consumed at {} is never produced and starts at zero", name, location),
+            MarleaParserError::UnusedSpecies { location, name } => write!(f, "species {} produced at {} is never consumed", name, location),
+            MarleaParserError::NearMissName { location, name, suggestion } => write!(f, "species {} at {} is named like {}, did you mean {}?", name, location, suggestion, suggestion),
+            MarleaParserError::TemplateArguments { location, name, expected, found } => write!(f, "template {} at {} takes {} arguments but {} were given", name, location, expected, found),
+            MarleaParserError::MissingInclude { location, path, source } => write!(f, "failed to read {} included at {}: {}", path.display(), location, source),
+            MarleaParserError::IncludeCycle { location, path } => write!(f, "{} included at {} includes itself", path.display(), location),
+            MarleaParserError::UnresolvedInclude { location, path } => write!(f, "cannot resolve include {} at {} without a source file", path, location),
+            MarleaParserError::Xml { location, source } => write!(f, "malformed xml at {}: {}", location, source),
+            MarleaParserError::InvalidSbml { location, reason } => write!(f, "invalid sbml at {}: {}", location, reason),
+            MarleaParserError::UnsupportedSbml { location, construct } => write!(f, "unsupported sbml {} at {}", construct, location),
+            MarleaParserError::NotMassAction { location, reaction } => write!(f, "reaction {} at {} does not have a mass action kinetic law", reaction, location),
+            MarleaParserError::Antimony { location, source } => write!(f, "antimony syntax error at {}\n{}", location, source),
+            MarleaParserError::InvalidAntimony { location, reason } => write!(f, "invalid antimony at {}: {}", location, reason),
+            MarleaParserError::UnsupportedAntimony { location, construct } => write!(f, "unsupported antimony {} at {}", construct, location),
+            MarleaParserError::InvalidBionetgen { location, reason } => write!(f, "invalid BioNetGen network at {}: {}", location, reason),
+            MarleaParserError::UnsupportedBionetgen { location, construct } => write!(f, "unsupported BioNetGen {} at {}", construct, location),
+            MarleaParserError::Json { location, source } => write!(f, "malformed json at {}: {}", location, source),
+            MarleaParserError::Toml { location, source } => write!(f, "malformed toml at {}: {}", location, source.message()),
+            MarleaParserError::Yaml { location, source } => write!(f, "malformed yaml at {}: {}", location, source),
+            MarleaParserError::InvalidValue { file: Some(file), value_path, reason } => write!(f, "invalid value at {} in {}: {}", value_path, file.display(), reason),
+            MarleaParserError::InvalidValue { file: None, value_path, reason } => write!(f, "invalid value at {}: {}", value_path, reason),
+            MarleaParserError::UnexpectedToken { location, found, expected } => write!(f, "found unexpected {} token at {}, expected {} token", found, location, expected),
+            MarleaParserError::EmptyTokenStream { path: Some(path) } => write!(f, "{} was parsed but token stream is empty", path.display()),
+            MarleaParserError::EmptyTokenStream { path: None } => write!(f, "source was parsed but token stream is empty"),
+            MarleaParserError::Io { path, source } => write!(f, "failed to read {}: {}", path.display(), source),
+            MarleaParserError::Decode { location, source } => write!(f, "non valid utf8 input at {}: {}", location, source),
+            MarleaParserError::UnsupportedExt { path } => write!(f, "provided file {} is not a supported format", path.display()),
+            MarleaParserError::InvalidFile { path } => write!(f, "provided path {} did not contain an extension or does not exist", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for MarleaParserError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MarleaParserError::Syntax { source, .. } => Some(source.as_ref()),
+            MarleaParserError::Io { source, .. } => Some(source),
+            MarleaParserError::MissingInclude { source, .. } => Some(source),
+            MarleaParserError::Xml { source, .. } => Some(source.as_ref()),
+            MarleaParserError::Antimony { source, .. } => Some(source.as_ref()),
+            MarleaParserError::Json { source, .. } => Some(source.as_ref()),
+            MarleaParserError::Toml { source, .. } => Some(source.as_ref()),
+            MarleaParserError::Yaml { source, .. } => Some(source.as_ref()),
+            MarleaParserError::Decode { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<PestError<Rule>> for MarleaParserError {
+    fn from(error: PestError<Rule>) -> Self {
+        MarleaParserError::Syntax { location: SourceSpan::from_pest_error(&error), source: Box::new(error) }
+    }
+}
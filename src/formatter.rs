@@ -0,0 +1,269 @@
+//! Rewrites csv network files into a canonical layout without changing the network they describe.
+//!
+//! Terms are separated by ` + `, reactants and products by ` => ` or ` <=> `, and columns by a single comma with no spaces.
+//! Reaction and species count rows end in a comma while directive rows such as `#param` do not.
+//! Comments are kept on the row they were written on as `,//comment,`, rows holding only commas become `,,`
+//! and empty rows stay empty, so the sections of a file are preserved. Formatting is idempotent.
+//! Rows are formatted from the [SyntaxTree] of the source so nothing the author wrote is lost.
+//!
+//! ```text
+//! ,//Setup RXN Network,
+//! fibonacci.call  =>  setup.call ,1,       becomes       fibonacci.call => setup.call,1,
+//! destruct+2 next_value => next_value,10000          destruct + 2 next_value => next_value,10000,
+//! ```
+
+use pest::{Parser, Span, iterators::Pair};
+
+use crate::{CSVparser, MarleaParserError, Rule, syntax::{StatementKind, SyntaxRow, SyntaxTerm, SyntaxTree}};
+
+/// Formats csv source text, see the [module documentation](self) for the canonical layout
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CSVformatter {
+    align_rates: bool,
+}
+
+/// a formatted row before rate columns are aligned
+#[derive(Debug, Default)]
+struct Row {
+    // statement text up to the rate column, empty for comment and blank rows
+    statement: String,
+    // the rates of a reaction row, which are the columns aligned
+    rates: Option<String>,
+    // comment text starting with `//`
+    comment: Option<String>,
+    // whether the row ends in a comma, directives such as #param do not
+    trailing_comma: bool,
+    // whether a row without a statement or comment held separator commas
+    separator: bool,
+}
+
+impl CSVformatter {
+    pub fn new() -> Self {
+        CSVformatter { align_rates: false }
+    }
+
+    /// pad consecutive reaction rows so their rates start in the same column
+    pub fn align_rates(mut self, align_rates: bool) -> Self {
+        self.align_rates = align_rates;
+        self
+    }
+
+    /// format csv source text, failing with a description of the first row which does not parse
+    pub fn format(&self, source: &str) -> Result<String, MarleaParserError> {
+        let tree = SyntaxTree::parse(source)?;
+        let mut rows = tree.rows.iter()
+            .map(|syntax_row| Self::format_row(source, syntax_row))
+            .collect::<Result<Vec<Row>, MarleaParserError>>()?;
+
+        // rows after the last statement or comment are not separating anything
+        while rows.last().is_some_and(|row| row.statement.is_empty() && row.comment.is_none()) {
+            rows.pop();
+        }
+
+        if self.align_rates {
+            Self::align(&mut rows);
+        }
+
+        Result::Ok(rows.iter().map(|row| Self::render(row) + "\n").collect())
+    }
+
+    fn format_row(source: &str, syntax_row: &SyntaxRow) -> Result<Row, MarleaParserError> {
+        let mut row = Row {
+            comment: syntax_row.comment.as_ref().map(|comment| comment.text.trim_end_matches([',', ' ', '\t']).to_string()),
+            ..Row::default()
+        };
+
+        let statement = match &syntax_row.statement {
+            Some(statement) => statement,
+            None => {
+                row.separator = syntax_row.text.contains(',');
+                return Result::Ok(row);
+            },
+        };
+
+        match &statement.kind {
+            StatementKind::Reaction { reactants, products, rate } => {
+                row.statement = format!("{} => {}", Self::format_side(reactants), Self::format_side(products));
+                row.rates = Some(Self::format_expression(rate));
+                row.trailing_comma = true;
+            },
+            StatementKind::ReversibleReaction { reactants, products, forward_rate, reverse_rate } => {
+                row.statement = format!("{} <=> {}", Self::format_side(reactants), Self::format_side(products));
+                row.rates = Some(format!("{},{}", Self::format_expression(forward_rate), Self::format_expression(reverse_rate)));
+                row.trailing_comma = true;
+            },
+            StatementKind::SpeciesCount { name, count } => {
+                row.statement = format!("{},{}", name, Self::format_expression(count));
+                row.trailing_comma = true;
+            },
+            StatementKind::Parameter { name, value } => row.statement = format!("#param {} = {}", name, Self::format_expression(value)),
+            StatementKind::Include { path } => row.statement = format!("#include \"{}\"", path),
+            StatementKind::Instance { template, arguments } => row.statement = format!("#use {}({})", template, arguments.join(", ")),
+            StatementKind::Template { name, parameters } => row.statement = format!("#template {}({})", name, parameters.join(", ")),
+            StatementKind::TemplateEnd => row.statement = String::from("#end"),
+            StatementKind::Malformed => {
+                // the span came from parsing this same source so always lies within it
+                let malformed_row = Span::new(source, statement.span.start, statement.span.end).ok_or(MarleaParserError::EmptyTokenStream { path: None })?;
+                return Result::Err(CSVparser::describe_malformed_row(source, malformed_row));
+            },
+        }
+        Result::Ok(row)
+    }
+
+    /// terms separated by plus signs, or NULL for a side with no terms
+    fn format_side(terms: &[SyntaxTerm]) -> String {
+        if terms.is_empty() {
+            return String::from("NULL");
+        }
+
+        terms.iter()
+        .map(|term| match &term.coefficient {
+            Some(coefficient) => format!("{} {}", Self::format_coefficient(coefficient), term.name),
+            None => term.name.clone(),
+        })
+        .collect::<Vec<String>>()
+        .join(" + ")
+    }
+
+    /// a coefficient in brackets is an expression, any other is a number or parameter written as is
+    fn format_coefficient(coefficient: &str) -> String {
+        match CSVparser::parse(Rule::coefficient, coefficient).ok().and_then(|mut tokens| tokens.next()?.into_inner().next()) {
+            Some(expression) if expression.as_rule() == Rule::expression => format!("({})", Self::format_tokens(expression)),
+            _ => coefficient.to_string(),
+        }
+    }
+
+    /// normalize the spacing of expression text taken from the syntax tree
+    fn format_expression(expression: &str) -> String {
+        match CSVparser::parse(Rule::expression, expression).ok().and_then(|mut tokens| tokens.next()) {
+            Some(expression) => Self::format_tokens(expression),
+            None => expression.to_string(),
+        }
+    }
+
+    /// an expression with single spaces around binary operators and none inside brackets, ie `(n + 1) / 2`
+    fn format_tokens(expression: Pair<'_, Rule>) -> String {
+        let mut output = String::new();
+        for sub_token in expression.into_inner() {
+            match sub_token.as_rule() {
+                Rule::expression => output.push_str(&format!("({})", Self::format_tokens(sub_token))),
+                Rule::add | Rule::subtract | Rule::multiply | Rule::divide | Rule::power => output.push_str(&format!(" {} ", sub_token.as_str())),
+                _ => output.push_str(sub_token.as_str()),
+            }
+        }
+        output
+    }
+
+    /// pad the statement of each run of consecutive reaction rows to the widest in the run
+    fn align(rows: &mut [Row]) {
+        for run in rows.split_mut(|row| row.rates.is_none()) {
+            let width = run.iter().map(|row| row.statement.chars().count()).max().unwrap_or(0);
+            for row in run {
+                let padding = width - row.statement.chars().count();
+                row.rates = row.rates.take().map(|rates| " ".repeat(padding) + &rates);
+            }
+        }
+    }
+
+    fn render(row: &Row) -> String {
+        let mut output = row.statement.clone();
+        if let Some(rates) = &row.rates {
+            output.push(',');
+            output.push_str(rates);
+        }
+
+        match (&row.comment, row.statement.is_empty()) {
+            (Some(comment), _) => output.push_str(&format!(",{},", comment)),
+            (None, false) if row.trailing_comma => output.push(','),
+            (None, true) if row.separator => output.push_str(",,"),
+            _ => (),
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{CSVparser, MarleaParser, formatter::CSVformatter};
+
+    #[test]
+    fn formatter_normalizes_spacing() {
+        let source = concat!(
+            ",//Setup RXN Network,\n",
+            "fibonacci.call  =>  setup.call ,1, \n",
+            "destruct+2 next_value => next_value + destruct ,10000\n",
+            ",,\n",
+            "\n",
+            "#param   fast=2^ (n+1)  ,// doubles ,,\n",
+            "#param n = 3\n",
+            "A <=> NULL,fast*2,- 1+2,\n",
+            "A , 5 ,\n",
+            "  ,,,\n",
+        );
+        assert_eq!(CSVformatter::new().format(source).unwrap(), concat!(
+            ",//Setup RXN Network,\n",
+            "fibonacci.call => setup.call,1,\n",
+            "destruct + 2 next_value => next_value + destruct,10000,\n",
+            ",,\n",
+            "\n",
+            "#param fast = 2 ^ (n + 1),// doubles,\n",
+            "#param n = 3\n",
+            "A <=> NULL,fast * 2,-1 + 2,\n",
+            "A,5,\n",
+        ));
+    }
+
+    #[test]
+    fn formatter_aligns_rates_and_keeps_templates() {
+        let source = concat!(
+            "#template not(X,Y) ,//not gate\n",
+            "X => NULL,1\n",
+            "2 X.index.0 => X.index.1,fast\n",
+            "#use other( a ,b )\n",
+            "#end\n",
+            "A => B,1,\n",
+        );
+        assert_eq!(CSVformatter::new().align_rates(true).format(source).unwrap(), concat!(
+            "#template not(X, Y),//not gate,\n",
+            "X => NULL,               1,\n",
+            "2 X.index.0 => X.index.1,fast,\n",
+            "#use other(a, b)\n",
+            "#end\n",
+            "A => B,1,\n",
+        ));
+    }
+
+    #[test]
+    fn formatter_is_idempotent_on_fibonacci() {
+        let source = MarleaParser::read_source(&Path::new("test_data").join("Fibonacci_calculator.csv")).unwrap();
+
+        for formatter in [CSVformatter::new(), CSVformatter::new().align_rates(true)] {
+            let formatted = formatter.format(&source).unwrap();
+            assert_eq!(formatter.format(&formatted).unwrap(), formatted);
+
+            // formatting never changes the network
+            let original = CSVparser::as_reaction_network(&source).unwrap();
+            let reformatted = CSVparser::as_reaction_network(&formatted).unwrap();
+            assert_eq!(original.get_reactions(), reformatted.get_reactions());
+            assert_eq!(original.get_solution().species_counts, reformatted.get_solution().species_counts);
+            assert!(formatted.contains(",//Setup RXN Network,\n"));
+        }
+    }
+
+    #[test]
+    fn formatter_accepts_what_the_parser_accepts() {
+        for source in [",A,5,\n", ",,//header,\n,A => B,1,\nA,5,\n"] {
+            assert!(CSVparser::as_reaction_network(source).is_ok());
+            let formatted = CSVformatter::new().format(source).unwrap();
+            assert_eq!(CSVparser::as_reaction_network(&formatted).unwrap().get_reactions(), CSVparser::as_reaction_network(source).unwrap().get_reactions());
+        }
+        assert_eq!(CSVformatter::new().format(",A,5,\n").unwrap(), "A,5,\n");
+    }
+
+    #[test]
+    fn formatter_rejects_invalid_source() {
+        assert!(CSVformatter::new().format("A => => B,1,\n").is_err());
+    }
+}
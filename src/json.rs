@@ -0,0 +1,216 @@
+//! Reads and writes reaction networks as json documents.
+//!
+//! A network is an object with a `species` array of initial counts and a `reactions` array,
+//! each of which maps directly onto the engine's [Name], [Count], [Term] and [Reaction] types.
+//!
+//! ```json
+//! {
+//!   "species": [
+//!     { "name": "A", "count": 5 }
+//!   ],
+//!   "reactions": [
+//!     {
+//!       "reactants": [{ "name": "A", "coefficient": 1 }, { "name": "B", "coefficient": 2 }],
+//!       "products": [{ "name": "C", "coefficient": 1 }],
+//!       "rate": 10
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Both arrays may be omitted, as may a reaction's `reactants` or `products` which then default to no terms,
+//! and a term's `coefficient` which defaults to 1. Species used by a reaction without an entry in `species` start at zero.
+//! Counts, coefficients and rates are whole numbers, and coefficients and rates must be positive.
+//! Any value which does not match the schema is reported with its path in the document such as `$.reactions[2].rate`.
+//! The same schema is used for toml and yaml documents, see [crate::document].
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, reaction::{Reaction, term::Term}};
+
+use crate::writer::CSVwriter;
+
+/// A reaction network as it appears in a json document.
+/// Any top level field other than `species`, `reactions` and a free form `metadata` value is rejected, as it is by [crate::MarleaParser::parse].
+/// The parser also accepts `species` written as a table of names to counts and rejects zero rates and coefficients,
+/// which these types do not check
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonNetwork {
+    #[serde(default)]
+    pub species: Vec<JsonSpecies>,
+    #[serde(default)]
+    pub reactions: Vec<JsonReaction>,
+    /// kept as written, the network does not use it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// initial count of a single species
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonSpecies {
+    pub name: String,
+    pub count: u64,
+}
+
+/// a reaction with its reactant and product terms
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonReaction {
+    #[serde(default)]
+    pub reactants: Vec<JsonTerm>,
+    #[serde(default)]
+    pub products: Vec<JsonTerm>,
+    pub rate: u64,
+}
+
+/// a species and how many of it a reaction consumes or produces
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonTerm {
+    pub name: String,
+    #[serde(default = "JsonTerm::default_coefficient")]
+    pub coefficient: u64,
+}
+
+impl JsonTerm {
+    fn default_coefficient() -> u64 {
+        1
+    }
+}
+
+/// Serializes a [ReactionNetwork] into a json document
+pub struct JSONwriter;
+
+impl JSONwriter {
+    /// convert the network into its json schema, species are sorted by name and reactions by their csv row so output is deterministic
+    pub fn as_document(reaction_network: &ReactionNetwork) -> JsonNetwork {
+        let mut species = reaction_network.get_solution().species_counts.iter()
+            .map(|(name, count)| JsonSpecies { name: name.0.clone(), count: count.0 })
+            .collect::<Vec<JsonSpecies>>();
+        species.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+        let mut reactions = reaction_network.get_reactions().iter().collect::<Vec<&Reaction>>();
+        reactions.sort_by_cached_key(|reaction| CSVwriter::as_row(reaction));
+        let reactions = reactions.into_iter()
+            .map(|reaction| JsonReaction {
+                reactants: Self::as_terms(reaction.get_reactants()),
+                products: Self::as_terms(reaction.get_products()),
+                rate: reaction.get_reaction_rate(),
+            })
+            .collect();
+
+        JsonNetwork { species, reactions, metadata: None }
+    }
+
+    /// write the network as pretty printed json text
+    pub fn as_json(reaction_network: &ReactionNetwork) -> String {
+        let mut output = serde_json::to_string_pretty(&Self::as_document(reaction_network)).unwrap_or_default();
+        output.push('\n');
+        output
+    }
+
+    /// write the network as pretty printed json text to any writer such as a file
+    pub fn write(reaction_network: &ReactionNetwork, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(Self::as_json(reaction_network).as_bytes())
+    }
+
+    fn as_terms(terms: &[Term]) -> Vec<JsonTerm> {
+        terms.iter()
+        .map(|term| JsonTerm { name: term.get_species_name().0.clone(), coefficient: term.get_coefficient().0 })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use marlea_engine::trial::reaction_network::solution::{Name, Count};
+
+    use crate::{MarleaParser, MarleaParserError, document::{DocumentFormat, DocumentParser}, json::{JSONwriter, JsonNetwork}};
+
+    #[test]
+    fn json_parser_reads_schema() {
+        let parsed = MarleaParser::parse(&Path::new("test_data").join("json").join("network.json")).unwrap();
+        let expected = MarleaParser::parse_str("A + 2 B => C,10,\nC => NULL,1,\nNULL => A,2.5e1,\nA,5,\nB,3,\n").unwrap();
+        assert_eq!(parsed.get_reactions(), expected.get_reactions());
+        assert_eq!(parsed.get_solution().species_counts, expected.get_solution().species_counts);
+        assert_eq!(parsed.get_solution().species_counts.get(&Name(String::from("C"))), Some(&Count(0)));
+    }
+
+    #[test]
+    fn json_writer_round_trips_fibonacci() {
+        let parsed = MarleaParser::parse(&Path::new("test_data").join("Fibonacci_calculator.csv")).unwrap();
+
+        let written = JSONwriter::as_json(&parsed);
+        let reparsed = DocumentParser::as_reaction_network(DocumentFormat::Json, &written).unwrap();
+        assert_eq!(parsed.get_reactions(), reparsed.get_reactions());
+        assert_eq!(parsed.get_solution().species_counts, reparsed.get_solution().species_counts);
+        assert_eq!(JSONwriter::as_json(&reparsed), written);
+    }
+
+    #[test]
+    fn json_parser_reports_value_paths() {
+        let source = r#"{
+            "species": [{ "name": "A", "count": -1 }, { "name": "B", "count": 2 }],
+            "reactions": [
+                { "reactants": [{ "name": "A" }], "rate": 1 },
+                { "reactants": [{ "name": "A", "coefficient": "two" }], "products": [], "rate": 1 },
+                { "products": [{ "name": "A B" }], "rate": 1 },
+                { "reactants": [], "rate": 0 },
+                { "reactants": [], "products": [], "rtae": 1 }
+            ],
+            "parameters": {}
+        }"#;
+        let (network, diagnostics) = DocumentParser::as_reaction_network_recovering(DocumentFormat::Json, source);
+
+        // numbers are rejected with the csv errors, whose text starts with the path of the value
+        let paths = diagnostics.iter()
+            .map(|diagnostic| match diagnostic {
+                MarleaParserError::InvalidValue { value_path, .. } => value_path.as_str(),
+                MarleaParserError::ZeroRate { text, .. } | MarleaParserError::BadSpeciesCount { text, .. } => text.split(" = ").next().unwrap_or_default(),
+                other => panic!("unexpected diagnostic {}", other),
+            })
+            .collect::<Vec<&str>>();
+        // fields of an object are visited in sorted order
+        assert_eq!(paths, [
+            "$.parameters",
+            "$.reactions[1].reactants[0].coefficient",
+            "$.reactions[2].products[0].name",
+            "$.reactions[3].rate",
+            "$.reactions[4].rtae",
+            "$.species[0].count",
+        ]);
+
+        // the valid entries are still imported
+        assert_eq!(network.get_reactions().len(), 1);
+        assert_eq!(network.get_solution().species_counts.get(&Name(String::from("B"))), Some(&Count(2)));
+    }
+
+    #[test]
+    fn json_schema_types_reject_unknown_fields() {
+        let document = serde_json::from_str::<JsonNetwork>(r#"{ "reactions": [{ "rate": 1 }], "metadata": { "author": "A" } }"#).unwrap();
+        assert_eq!(document.reactions.len(), 1);
+        assert_eq!(document.metadata.unwrap()["author"], "A");
+
+        let unknown = r#"{ "reactions": [], "parameters": {} }"#;
+        assert!(serde_json::from_str::<JsonNetwork>(unknown).is_err());
+        assert!(DocumentParser::as_reaction_network(DocumentFormat::Json, unknown).is_err());
+    }
+
+    #[test]
+    fn json_parser_reports_syntax_location() {
+        let err = DocumentParser::as_reaction_network(DocumentFormat::Json, "{\n  \"species\": [,]\n}").unwrap_err();
+        match err {
+            MarleaParserError::Json { location, .. } => {
+                assert_eq!(location.line, 2);
+                assert_eq!(location.column, 15);
+            },
+            other => panic!("expected a json error, found {}", other),
+        }
+    }
+}
@@ -7,86 +7,515 @@
 /// Its purpose it to take a variety of plaintext source files such as .csv or .rs and compile a reaction network, 
 /// which may be simulated by the [MARlea_engine](https://github.com/nadaso8/MARlea_engine) module.
 
-use std::{collections::{HashMap, HashSet}, default, fs::File, io::Read, path::Path, str::{Bytes, from_utf8, Utf8Error}};
+use std::{collections::{HashMap, HashSet}, fs::File, io::Read, path::{Path, PathBuf}, str::from_utf8, sync::OnceLock};
 
-use pest::{Parser, iterators::Pair};
+use pest::{Parser, Position, Span, error::{Error as PestError, ErrorVariant, InputLocation}, iterators::Pair, pratt_parser::{Assoc, Op, PrattParser}};
 use pest_derive::Parser;
 
 use marlea_engine::trial::reaction_network::{ReactionNetwork, solution::{Name, Count, Solution}, reaction::{Reaction, term::Term}};
 
+mod error;
+mod number;
+mod mass_action;
+pub mod sbml;
+pub mod diagnostic;
+pub mod writer;
+pub mod stoichiometry;
+pub mod dot;
+pub mod formatter;
+pub mod syntax;
+pub mod section;
+pub mod json;
+mod document;
+pub mod antimony;
+mod bionetgen;
+pub mod lint;
+pub use error::{MarleaParserError, SourceSpan};
+use number::{ArithmeticError, IntegerConversionError, Rational};
+use sbml::SBMLparser;
+use document::{DocumentFormat, DocumentParser};
+use antimony::AntimonyParser;
+use bionetgen::BNGparser;
+use section::{Section, SectionedNetwork};
+use syntax::Comment;
+
 // derive parsers 
 #[derive(Parser)]
 #[grammar = "grammars/csv.pest"]
 struct CSVparser;
 
+// operator precedence for expression tokens, lowest first
+static EXPRESSION_PARSER: OnceLock<PrattParser<Rule>> = OnceLock::new();
+
+/// value declared by a `#param name = value` row
+struct Parameter {
+    value: Rational,
+    location: SourceSpan,
+}
+
+/// loads the network named by an `#include` row given its quoted path and the location of the row
+type IncludeResolver<'a> = dyn FnMut(&str, &SourceSpan) -> Result<NetworkBuilder, MarleaParserError> + 'a;
+
+/// reactions and species counts produced by expanding a template
+type Expansion = (Vec<Reaction>, Vec<(Name, Count)>);
+
+/// block of rows declared by `#template name(X, Y)` which is expanded by `#use name(a, b)` rows
+struct Template {
+    parameters: Vec<String>,
+    reactions: Vec<Reaction>,
+    species_counts: Vec<(Name, Count)>,
+    location: SourceSpan,
+}
+
+impl Template {
+    /// copy the template body, replacing every dot separated name segment which matches a parameter with its argument
+    fn instantiate (&self, arguments: &[&str]) -> Expansion {
+        let substitute = |name: &Name| -> Name {
+            Name(name.0.split('.')
+                .map(|segment| match self.parameters.iter().position(|parameter| parameter == segment) {
+                    Some(index) => arguments[index],
+                    None => segment,
+                })
+                .collect::<Vec<&str>>()
+                .join("."))
+        };
+        let substitute_terms = |terms: &Vec<Term>| -> Vec<Term> {
+            terms.iter()
+            .map(|term| Term::new(substitute(term.get_species_name()), term.get_coefficient().clone()))
+            .collect()
+        };
+
+        let reactions = self.reactions.iter()
+            .map(|reaction| Reaction::new(substitute_terms(reaction.get_reactants()), substitute_terms(reaction.get_products()), reaction.get_reaction_rate()))
+            .collect();
+        let species_counts = self.species_counts.iter()
+            .map(|(name, count)| (substitute(name), count.clone()))
+            .collect();
+
+        (reactions, species_counts)
+    }
+}
+
+/// a reaction or species count added to a network by a row
+#[derive(Clone)]
+enum Added {
+    Reaction(Reaction),
+    // the species whose initial count was set
+    SpeciesCount(Name),
+}
+
+/// what a builder records beyond the network itself
+#[derive(Debug, Clone, Copy, Default)]
+struct Tracking {
+    // group rows into sections by comment headers
+    sections: bool,
+    // keep everything added along with the row it came from
+    rows: bool,
+}
+
+/// accumulates the reactions and species counts interpreted from a token stream
+#[derive(Default)]
+struct NetworkBuilder {
+    reactions: HashSet<Reaction>,
+    // only counts set by species count rows, any other species starts at zero when built
+    species_counts: HashMap<Name, Count>,
+    // parameters are local to the file which declares them
+    parameters: HashMap<String, Parameter>,
+    // templates defined so far, including those from included files
+    templates: HashMap<String, Template>,
+    // rows grouped under the comment header above them, only tracked when sections were asked for
+    sections: Option<Vec<Section>>,
+    // the row being interpreted, which anything added is attributed to
+    row: Option<SourceSpan>,
+    // every reaction and species count in the order they were added with the row they came from, only tracked when linting,
+    // duplicates are kept here even though the network holds each reaction once
+    rows: Option<Vec<(Added, SourceSpan)>>,
+}
+
+impl NetworkBuilder {
+    fn new (tracking: Tracking) -> Self {
+        NetworkBuilder {
+            sections: tracking.sections.then(Vec::new),
+            rows: tracking.rows.then(Vec::new),
+            ..NetworkBuilder::default()
+        }
+    }
+
+    fn add_reaction (&mut self, reaction: Reaction) {
+        if let Some(section) = self.current_section() {
+            section.reactions.push(reaction.clone());
+        }
+        if let (Some(rows), Some(row)) = (&mut self.rows, &self.row) {
+            rows.push((Added::Reaction(reaction.clone()), row.clone()));
+        }
+        self.reactions.insert(reaction);
+    }
+
+    fn add_species_count (&mut self, species_count: (Name, Count)) {
+        if let Some(section) = self.current_section() {
+            section.species_counts.push(species_count.clone());
+        }
+        if let (Some(rows), Some(row)) = (&mut self.rows, &self.row) {
+            rows.push((Added::SpeciesCount(species_count.0.clone()), row.clone()));
+        }
+
+        // update or insert species (Name, Count) pair
+        self.species_counts.entry(species_count.0)
+        .and_modify(|count| *count = species_count.1.clone())
+        .or_insert(species_count.1);
+    }
+
+    /// the section rows are being added to, rows above the first header go in a section without one
+    fn current_section (&mut self) -> Option<&mut Section> {
+        let sections = self.sections.as_mut()?;
+        if sections.is_empty() {
+            sections.push(Section::new(None));
+        }
+        sections.last_mut()
+    }
+
+    fn start_section (&mut self, header: Comment) {
+        if let Some(sections) = &mut self.sections {
+            sections.push(Section::new(Some(header)));
+        }
+    }
+
+    fn declare_parameter (&mut self, name: String, parameter: Parameter) -> Result<(), MarleaParserError> {
+        match self.parameters.get(&name) {
+            Some(previous) => Result::Err(MarleaParserError::DuplicateParameter { location: parameter.location, name, previous: Box::new(previous.location.clone()) }),
+            None => {
+                self.parameters.insert(name, parameter);
+                Result::Ok(())
+            }
+        }
+    }
+
+    fn declare_template (&mut self, name: String, template: Template) -> Result<(), MarleaParserError> {
+        match self.templates.get(&name) {
+            Some(previous) => Result::Err(MarleaParserError::DuplicateTemplate { location: template.location, name, previous: Box::new(previous.location.clone()) }),
+            None => {
+                self.templates.insert(name, template);
+                Result::Ok(())
+            }
+        }
+    }
+
+    /// merge an included network, its species counts replace any set above the include row
+    /// and its templates become usable below the include row
+    fn merge (&mut self, included: NetworkBuilder) {
+        // a file included more than once defines the same templates each time so the first definition is kept
+        for (name, template) in included.templates {
+            self.templates.entry(name).or_insert(template);
+        }
+
+        // the included rows are kept with their own locations rather than attributed to the include row
+        let row = self.row.take();
+        for reaction in included.reactions {
+            self.add_reaction(reaction);
+        }
+        for species_count in included.species_counts {
+            self.add_species_count(species_count);
+        }
+        self.row = row;
+        if let (Some(rows), Some(included_rows)) = (&mut self.rows, included.rows) {
+            rows.extend(included_rows);
+        }
+    }
+
+    /// set the file of every tracked row which does not have one yet
+    fn set_path (&mut self, path: &Path) {
+        for (_, row) in self.rows.iter_mut().flatten() {
+            row.path.get_or_insert_with(|| path.to_path_buf());
+        }
+    }
+
+    fn build (self) -> ReactionNetwork {
+        let mut species_counts = self.species_counts;
+
+        // loop over reactants and products and insert any names without a count into species_counts
+        for reaction in &self.reactions {
+            for term in reaction.get_reactants().iter().chain(reaction.get_products()) {
+                species_counts.entry(term.get_species_name().clone()).or_insert(Count(0));
+            }
+        }
+
+        ReactionNetwork::new(self.reactions, Solution{species_counts})
+    }
+
+    fn build_sectioned (mut self) -> SectionedNetwork {
+        let sections = self.sections.take().unwrap_or_default();
+        SectionedNetwork::new(self.build(), sections)
+    }
+}
+
 // functions for interpreting tokenstream output from CSVparser
 impl CSVparser {
     /// gen token stream and parse into a reaction network 
+    /// `#include` rows can not be resolved without a file path and are reported as errors, see [MarleaParser::parse]
     pub fn as_reaction_network(source: &str) -> Result<ReactionNetwork,MarleaParserError> {
+        Self::interpret_network(source, Tracking::default(), &mut Self::unresolved_include).map(NetworkBuilder::build)
+    }
+
+    /// gen token stream and parse into a reaction network, grouping rows into sections started by comment headers
+    fn as_sectioned_network(source: &str) -> Result<SectionedNetwork,MarleaParserError> {
+        Self::interpret_network(source, Tracking { sections: true, rows: false }, &mut Self::unresolved_include).map(NetworkBuilder::build_sectioned)
+    }
+
+    /// gen token stream and parse into a reaction network, skipping any rows which fail to parse or interpret.
+    /// Returns the network built from every valid row along with a diagnostic for each invalid one. 
+    pub fn as_reaction_network_recovering(source: &str) -> (ReactionNetwork, Vec<MarleaParserError>) {
+        let (builder, diagnostics) = Self::interpret_network_recovering(source, &mut Self::unresolved_include);
+        (builder.build(), diagnostics)
+    }
+
+    /// whether text is a single species name which a csv row could contain
+    fn is_name (text: &str) -> bool {
+        // NULL matches the name rule but denotes an empty side of a reaction
+        text != "NULL" && match Self::parse(Rule::name, text) {
+            Ok(mut tokens) => tokens.next().is_some_and(|token| token.as_str() == text),
+            Err(_) => false,
+        }
+    }
+
+    fn unresolved_include (path: &str, location: &SourceSpan) -> Result<NetworkBuilder, MarleaParserError> {
+        Result::Err(MarleaParserError::UnresolvedInclude { location: location.clone(), path: path.to_string() })
+    }
+
+    fn interpret_network (source: &str, tracking: Tracking, resolve: &mut IncludeResolver) -> Result<NetworkBuilder,MarleaParserError> {
         return match Self::parse(Rule::reaction_network, &source) {
             Ok(mut token_stream) => {
-                let mut reactions = HashSet::new();
-                let mut species_counts = HashMap::new();        
+                let mut builder = NetworkBuilder::new(tracking);
                 let reaction_network = match token_stream.next() {
                     Some(token) => token,
-                    None => return Result::Err(MarleaParserError::ParseFailed(format!("Source file was parsed but token stream is empty")))
+                    None => return Result::Err(MarleaParserError::EmptyTokenStream { path: None })
                 };
 
+                // parameters may be used above their declaration so they are collected before interpreting other rows
+                for token in reaction_network.clone().into_inner() {
+                    if let Err(msg) = Self::declare_parameter(token, &mut builder) {
+                        return Result::Err(msg);
+                    }
+                }
+
                 for token in reaction_network.into_inner() {
-                    match token.as_rule() {
-                        Rule::reaction => {
-                            // parse reaction token into a reaction object
-                            let reaction = match Self::as_reaction(token) {
-                                Result::Ok(reaction) => reaction,
-                                Result::Err(msg) => return Result::Err(msg)
-                            };
+                    if let Err(msg) = Self::interpret_row(source, token, &mut builder, resolve) {
+                        return Result::Err(msg);
+                    }
+                }
+
+                Result::Ok(builder)
+            },
+            // error if pest fails to match a reaction network token this should catch basically everything and contains the most information back to the user
+            Err(msg) => Result::Err(MarleaParserError::from(msg))
+        }
+    }
 
-                            reactions.insert(reaction.clone());
+    fn interpret_network_recovering (source: &str, resolve: &mut IncludeResolver) -> (NetworkBuilder, Vec<MarleaParserError>) {
+        let mut builder = NetworkBuilder::default();
+        let mut diagnostics = Vec::new();
 
-                            // loop over reactants and products and try to insert any names into species_counts
-                            for term in reaction.get_reactants() {
-                                species_counts.entry(term.get_species_name().clone()).or_insert(Count(0));
-                            }
-                            for term in reaction.get_products() {
-                                species_counts.entry(term.get_species_name().clone()).or_insert(Count(0));    
+        match Self::parse(Rule::recovering_network, source) {
+            Ok(mut token_stream) => {
+                match token_stream.next() {
+                    Some(recovering_network) => {
+                        for token in recovering_network.clone().into_inner() {
+                            if let Err(msg) = Self::declare_parameter(token, &mut builder) {
+                                diagnostics.push(msg);
                             }
+                        }
 
-                        },
-                        Rule::species_count => {
-                            // parse species_count token into a (Name, Count) pair 
-                            let species_count = match Self::as_species_count(token) {
-                                Result::Ok(species_count) => species_count,
-                                Result::Err(msg) =>  return Result::Err(msg),
+                        for token in recovering_network.into_inner() {
+                            let result = match token.as_rule() {
+                                Rule::malformed_row => Result::Err(Self::describe_malformed_row(source, token.as_span())),
+                                _ => Self::interpret_row(source, token, &mut builder, resolve),
                             };
-                            
-                            // update or insert species (Name, Count) pair
-                            species_counts.entry(species_count.0)
-                            .and_modify(|count| *count = species_count.1.clone())
-                            .or_insert(species_count.1);
-                        },
-                        _ => ()
-                    };
+                            if let Err(msg) = result {
+                                diagnostics.push(msg);
+                            }
+                        }
+                    },
+                    None => diagnostics.push(MarleaParserError::EmptyTokenStream { path: None }),
                 }
+            },
+            // every line matches malformed_row so this should only be reached if the grammar itself is broken
+            Err(msg) => diagnostics.push(MarleaParserError::from(msg)),
+        }
 
-                Result::Ok(ReactionNetwork::new(reactions, Solution{species_counts}))
+        (builder, diagnostics)
+    }
+
+    /// declare the parameter if token is a parameter row, any other row is ignored
+    fn declare_parameter (token: Pair<'_, Rule>, builder: &mut NetworkBuilder) -> Result<(), MarleaParserError> {
+        match token.as_rule() {
+            Rule::parameter => {
+                match Self::as_parameter(token, &builder.parameters) {
+                    Result::Ok((name, parameter)) => builder.declare_parameter(name, parameter),
+                    Result::Err(msg) => Result::Err(msg),
+                }
             },
-            // error if pest fails to match a reaction network token this should catch basically everything and contains the most information back to the user
-            Err(msg) => Result::Err(MarleaParserError::ParseFailed(format!("{}", msg)))
+            _ => Result::Ok(())
+        }
+    }
+
+    /// interpret a single reaction or species count token and add it to the network being built
+    fn interpret_row (source: &str, token: Pair<'_, Rule>, builder: &mut NetworkBuilder, resolve: &mut IncludeResolver) -> Result<(), MarleaParserError> {
+        builder.row = Some(SourceSpan::from_pair(&token));
+        match token.as_rule() {
+            Rule::reaction | Rule::reversible_reaction => {
+                // parse reaction token into one or more reaction objects
+                match Self::as_reaction(token, &builder.parameters) {
+                    Result::Ok(reactions) => {
+                        for reaction in reactions {
+                            builder.add_reaction(reaction);
+                        }
+                    },
+                    Result::Err(msg) => return Result::Err(msg)
+                };
+            },
+            Rule::species_count => {
+                // parse species_count token into a (Name, Count) pair 
+                match Self::as_species_count(token, &builder.parameters) {
+                    Result::Ok(species_count) => builder.add_species_count(species_count),
+                    Result::Err(msg) =>  return Result::Err(msg),
+                };
+            },
+            Rule::template => {
+                // templates must be defined above the rows which use them
+                match Self::as_template(source, token, builder) {
+                    Result::Ok((name, template)) => {
+                        if let Err(msg) = builder.declare_template(name, template) {
+                            return Result::Err(msg);
+                        }
+                    },
+                    Result::Err(msg) => return Result::Err(msg),
+                };
+            },
+            Rule::instance => {
+                // expand the template in place of the use row
+                match Self::as_instance(token, &builder.templates) {
+                    Result::Ok((reactions, species_counts)) => {
+                        for reaction in reactions {
+                            builder.add_reaction(reaction);
+                        }
+                        for species_count in species_counts {
+                            builder.add_species_count(species_count);
+                        }
+                    },
+                    Result::Err(msg) => return Result::Err(msg),
+                };
+            },
+            Rule::include => {
+                // load the included network in place of the include row
+                let location = SourceSpan::from_pair(&token);
+                match token.into_inner().find(|sub_token| sub_token.as_rule() == Rule::include_path) {
+                    Some(path) => match resolve(path.as_str(), &location) {
+                        Result::Ok(included) => builder.merge(included),
+                        Result::Err(msg) => return Result::Err(msg),
+                    },
+                    None => return Result::Err(MarleaParserError::UnexpectedToken { location, found: "include", expected: "include_path" }),
+                };
+            },
+            Rule::comment => {
+                // a comment alone on its row is a section header
+                let start = token.as_span().start();
+                let line_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+                if source[line_start..start].chars().all(|character| character == ',' || character == ' ') {
+                    builder.start_section(Comment { text: token.as_str().to_string(), span: SourceSpan::from_pair(&token) });
+                }
+            },
+            // parameters are declared before any rows are interpreted
+            Rule::parameter => (),
+            _ => ()
+        };
+        Result::Ok(())
+    }
+
+    /// interpret a template block into its name and body, any use rows in the body are expanded immediately
+    fn as_template (source: &str, token: Pair<'_, Rule>, builder: &NetworkBuilder) -> Result<(String, Template), MarleaParserError> {
+        let location = SourceSpan::from_pair(&token);
+        let mut possible_name = None;
+        let mut parameters = Vec::new();
+        let mut reactions = Vec::new();
+        let mut species_counts = Vec::new();
+
+        for sub_token in token.into_inner() {
+            match sub_token.as_rule() {
+                Rule::parameter_name => possible_name = Some(sub_token.as_str().to_string()),
+                Rule::template_parameters => {
+                    parameters = sub_token.into_inner().map(|parameter| parameter.as_str().to_string()).collect();
+                },
+                Rule::reaction | Rule::reversible_reaction => reactions.extend(Self::as_reaction(sub_token, &builder.parameters)?),
+                Rule::species_count => species_counts.push(Self::as_species_count(sub_token, &builder.parameters)?),
+                Rule::instance => {
+                    let (instance_reactions, instance_species_counts) = Self::as_instance(sub_token, &builder.templates)?;
+                    reactions.extend(instance_reactions);
+                    species_counts.extend(instance_species_counts);
+                },
+                Rule::malformed_row => return Result::Err(Self::describe_malformed_row(source, sub_token.as_span())),
+                Rule::comment => (),
+                _ => return Result::Err(Self::unexpected_token(&sub_token, Rule::template_row)),
+            }
+        }
+
+        match possible_name {
+            Some(name) => Result::Ok((name, Template { parameters, reactions, species_counts, location })),
+            None => Result::Err(MarleaParserError::UnexpectedToken { location, found: "template", expected: "parameter_name" }),
+        }
+    }
+
+    /// expand a use row into the reactions and species counts of its template
+    fn as_instance (token: Pair<'_, Rule>, templates: &HashMap<String, Template>) -> Result<Expansion, MarleaParserError> {
+        let location = SourceSpan::from_pair(&token);
+        let mut sub_tokens = token.into_inner();
+        let name = match sub_tokens.next() {
+            Some(sub_token) if sub_token.as_rule() == Rule::parameter_name => sub_token.as_str().to_string(),
+            _ => return Result::Err(MarleaParserError::UnexpectedToken { location, found: "instance", expected: "parameter_name" }),
+        };
+        let arguments = sub_tokens.map(|argument| argument.as_str()).collect::<Vec<&str>>();
+
+        match templates.get(&name) {
+            Some(template) if template.parameters.len() == arguments.len() => Result::Ok(template.instantiate(&arguments)),
+            Some(template) => Result::Err(MarleaParserError::TemplateArguments { location, name, expected: template.parameters.len(), found: arguments.len() }),
+            None => Result::Err(MarleaParserError::UndefinedTemplate { location, name }),
         }
     }
 
-    fn as_reaction (token: Pair<'_, Rule>) -> Result<Reaction,MarleaParserError> {
+    /// re parse a malformed row on its own so pest can describe what was expected, 
+    /// then move the error back to where the row sits in the full source
+    fn describe_malformed_row (source: &str, row: Span<'_>) -> MarleaParserError {
+        let row_start = row.start();
+        let variant = match Self::parse(Rule::row, row.as_str()) {
+            Err(row_error) => {
+                let offset = match row_error.location {
+                    InputLocation::Pos(pos) => pos,
+                    InputLocation::Span((start, _)) => start,
+                };
+                match Position::new(source, row_start + offset) {
+                    Some(position) => return MarleaParserError::from(PestError::new_from_pos(row_error.variant, position)),
+                    None => row_error.variant,
+                }
+            },
+            Ok(_) => ErrorVariant::CustomError { message: String::from("row could not be parsed as part of a reaction network") },
+        };
+        MarleaParserError::from(PestError::new_from_span(variant, row))
+    }
+
+    /// parse a reaction token into a reaction, 
+    /// or a reversible reaction token into its forward and reverse reactions
+    fn as_reaction (token: Pair<'_, Rule>, parameters: &HashMap<String, Parameter>) -> Result<Vec<Reaction>,MarleaParserError> {
         match token.as_rule() {
-            Rule::reaction => {
+            Rule::reaction | Rule::reversible_reaction => {
+                let location = SourceSpan::from_pair(&token);
+                let reversible = token.as_rule() == Rule::reversible_reaction;
                 let mut reactants = Vec::new();
                 let mut products = Vec::new();
-                let mut possible_reaction_rate = None;
+                let mut reaction_rates = Vec::new();
 
                 for sub_token in token.into_inner() {
                     match sub_token.as_rule() {
                         Rule::reactants => {
                             for reactant_token in sub_token.into_inner() {
-                                match Self::as_term(reactant_token) {
+                                match Self::as_term(reactant_token, parameters) {
                                     Ok(term) => reactants.push(term),
                                     Err(msg) => return Result::Err(msg) 
                                 }
@@ -94,40 +523,45 @@ impl CSVparser {
                         },
                         Rule::products => {
                             for product_token in sub_token.into_inner() {
-                                match Self::as_term(product_token) {
+                                match Self::as_term(product_token, parameters) {
                                     Ok(term) => products.push(term),
                                     Err(msg) => return Result::Err(msg) 
                                 }
                             }
                         }, 
                         Rule::reaction_rate => {
-                            possible_reaction_rate = Some(match Self::as_reaction_rate(sub_token) {
-                                Ok(reaction_rate) => reaction_rate,
-                                Err(msg) => return Result::Err(msg)
-                            })
+                            reaction_rates.push(Self::as_reaction_rate(sub_token, parameters)?)
                         },
                         _ => ()
                     }
                 }
 
-                match possible_reaction_rate {
-                    Some(reaction_rate) => {
-                        Result::Ok(Reaction::new(
+                match (reversible, reaction_rates.as_slice()) {
+                    (false, [reaction_rate]) => {
+                        Result::Ok(vec![Reaction::new(
                             reactants, 
                             products, 
                             reaction_rate.0
-                        ))
-                    }
-                    None => Result::Err(MarleaParserError::ParseFailed(format!("could not find reaction rate in reaction token stream")))
+                        )])
+                    },
+                    (true, [forward_rate, reverse_rate]) => {
+                        Result::Ok(vec![
+                            Reaction::new(reactants.clone(), products.clone(), forward_rate.0),
+                            Reaction::new(products, reactants, reverse_rate.0),
+                        ])
+                    },
+                    _ => Result::Err(MarleaParserError::MissingRate { location })
                 }
             },
-            _ => Result::Err(MarleaParserError::ParseFailed(format!("found unexpected {} token {}, expected reaction token", Self::rule_as_str(token.as_rule()), token.as_str()))),
+            _ => Result::Err(Self::unexpected_token(&token, Rule::reaction)),
         }
     }
 
-    fn as_term (token: Pair<'_, Rule>) -> Result<Term,MarleaParserError> {
+    fn as_term (token: Pair<'_, Rule>, parameters: &HashMap<String, Parameter>) -> Result<Term,MarleaParserError> {
         match token.as_rule() {
             Rule::term => {
+                let location = SourceSpan::from_pair(&token);
+                let text = token.as_str().to_string();
                 let mut possible_term: (Option<Name>, Option<Count>) = (None, None);
                 for sub_token in token.into_inner() {
                     match sub_token.as_rule() {
@@ -143,11 +577,7 @@ impl CSVparser {
                             };
                         },
                         Rule::coefficient => {
-                            let coefficient = match Self::as_count(sub_token) {
-                                Ok(val) => val,
-                                Err(msg) => return Result::Err(msg)
-                            };
-                            possible_term.1 = Some(coefficient);
+                            possible_term.1 = Some(Self::as_count(sub_token, parameters)?);
                         },
                         _ => ()
                     }
@@ -155,10 +585,10 @@ impl CSVparser {
 
                 match possible_term {
                     (Some(species_name), Some(coefficient)) => Result::Ok(Term::new(species_name, coefficient)),
-                    _ => Result::Err(MarleaParserError::ParseFailed(format!("missing data for term in token stream")))
+                    _ => Result::Err(MarleaParserError::BadTerm { location, text })
                 }
             },
-            _ => Result::Err(MarleaParserError::ParseFailed(format!("found unexpected {} token {}, expected term token", Self::rule_as_str(token.as_rule()), token.as_str()))),
+            _ => Result::Err(Self::unexpected_token(&token, Rule::term)),
         }
     } 
 
@@ -167,39 +597,76 @@ impl CSVparser {
             Rule::name => {
                 Result::Ok(Name(token.as_str().to_string()))
             },
-            _ => Result::Err(MarleaParserError::ParseFailed(format!("found unexpected {} token {}, expected name token", Self::rule_as_str(token.as_rule()), token.as_str()))),
+            _ => Result::Err(Self::unexpected_token(&token, Rule::name)),
         }
     } 
 
-    fn as_count (token: Pair<'_, Rule>) -> Result<Count,MarleaParserError> {
+    fn as_count (token: Pair<'_, Rule>, parameters: &HashMap<String, Parameter>) -> Result<Count,MarleaParserError> {
         match token.as_rule() {
             Rule::coefficient => {
-                if let Ok(count) = token.as_str().parse() {
-                    Result::Ok(Count(count))
-                } else {
-                    // if this error is ever returned you are &$&^%#
-                    Result::Err(MarleaParserError::ParseFailed(format!("something has gone seriously wrong at line {} input {}\nUnparseable character discovered ", token.line_col().0 , token.as_str())))
-                }
+                let location = SourceSpan::from_pair(&token);
+                let text = token.as_str().to_string();
+                let value = match token.clone().into_inner().next() {
+                    Some(sub_token) if sub_token.as_rule() == Rule::parameter_name => Self::resolve_parameter(sub_token, parameters),
+                    Some(sub_token) => Self::evaluate(sub_token, parameters),
+                    // a plain number has no inner tokens
+                    None => Rational::from_decimal(token.as_str()).map_err(|err| Self::arithmetic_error(err, &token)),
+                }?;
+
+                Self::as_coefficient(value, location, text)
             },
-            _ => Result::Err(MarleaParserError::ParseFailed(format!("found unexpected {} token {}, expected coefficient token", Self::rule_as_str(token.as_rule()), token.as_str()))),
+            _ => Result::Err(Self::unexpected_token(&token, Rule::coefficient)),
         }
     } 
 
-    fn as_reaction_rate (token: Pair<'_, Rule>) -> Result<Count,MarleaParserError> {
+    /// a coefficient must be a positive whole number.
+    /// Documents build their networks through these conversions too, so a value is checked the same way in every format
+    fn as_coefficient (value: Rational, location: SourceSpan, text: String) -> Result<Count, MarleaParserError> {
+        match value.to_u64() {
+            // a parameter may be zero but a term must consume or produce something
+            Ok(0) => Result::Err(MarleaParserError::BadCoefficient { location, text }),
+            Ok(coefficient) => Result::Ok(Count(coefficient)),
+            Err(IntegerConversionError::NotIntegral) => Result::Err(MarleaParserError::NonIntegral { location, text }),
+            Err(_) => Result::Err(MarleaParserError::BadCoefficient { location, text }),
+        }
+    }
+
+    /// a reaction rate must be a positive whole number
+    fn as_rate (value: Rational, location: SourceSpan, text: String) -> Result<u64, MarleaParserError> {
+        match value.to_u64() {
+            Ok(0) => Result::Err(MarleaParserError::ZeroRate { location, text }),
+            Ok(reaction_rate) => Result::Ok(reaction_rate),
+            Err(IntegerConversionError::NotIntegral) => Result::Err(MarleaParserError::InexactRate { location, text }),
+            Err(_) => Result::Err(MarleaParserError::BadRate { location, text }),
+        }
+    }
+
+    /// an initial species count may be any whole number including zero
+    fn as_initial_count (value: Rational, location: SourceSpan, text: String) -> Result<Count, MarleaParserError> {
+        match value.to_u64() {
+            Ok(count) => Result::Ok(Count(count)),
+            Err(IntegerConversionError::NotIntegral) => Result::Err(MarleaParserError::NonIntegral { location, text }),
+            Err(_) => Result::Err(MarleaParserError::BadSpeciesCount { location, text }),
+        }
+    }
+
+    fn as_reaction_rate (token: Pair<'_, Rule>, parameters: &HashMap<String, Parameter>) -> Result<Count,MarleaParserError> {
         match token.as_rule() {
             Rule::reaction_rate => {
-                if let Ok(reaction_rate) = token.as_str().parse() {
-                    Result::Ok(Count(reaction_rate))
-                } else {
-                    // if this error is ever returned you are &$&^%# 
-                    Result::Err(MarleaParserError::ParseFailed(format!("something has gone seriously wrong at line {} input {}\nUnparseable character discovered", token.line_col().0 , token.as_str())))
-                }
+                let location = SourceSpan::from_pair(&token);
+                let text = token.as_str().to_string();
+                let reaction_rate = match token.into_inner().next() {
+                    Some(expression) => Self::evaluate(expression, parameters).map_err(MarleaParserError::into_rate_error)?,
+                    None => return Result::Err(MarleaParserError::MissingRate { location }),
+                };
+
+                Self::as_rate(reaction_rate, location, text).map(Count)
             },
-            _ => Result::Err(MarleaParserError::ParseFailed(format!("found unexpected {} token {}, expected reaction rate token", Self::rule_as_str(token.as_rule()), token.as_str()))),
+            _ => Result::Err(Self::unexpected_token(&token, Rule::reaction_rate)),
         }
     }
     
-    fn as_species_count (token: Pair<'_, Rule>) -> Result<(Name, Count), MarleaParserError> {
+    fn as_species_count (token: Pair<'_, Rule>, parameters: &HashMap<String, Parameter>) -> Result<(Name, Count), MarleaParserError> {
         match token.as_rule() {
             Rule::species_count => {
             let mut possible_name = Option::None;
@@ -215,11 +682,10 @@ impl CSVparser {
                             Err(msg) => return Result::Err(msg)
                         }
                     }, 
-                    Rule::coefficient => {
-                        possible_count = match Self::as_count(sub_token) {
-                            Ok(count) => Some(count),
-                            Err(msg) => return Result::Err(msg)
-                        }
+                    Rule::expression => {
+                        let location = SourceSpan::from_pair(&sub_token);
+                        let text = sub_token.as_str().to_string();
+                        possible_count = Some(Self::as_initial_count(Self::evaluate(sub_token, parameters)?, location, text)?);
                     },
                     _ => ()
                 }
@@ -227,20 +693,151 @@ impl CSVparser {
 
             return match (possible_name, possible_count) {
                 (Some(name), Some(count)) => Result::Ok((name, count)),
-                _ => Result::Err(MarleaParserError::ParseFailed(format!("something has gone seriously wrong\nmissing name or count in {} on line {}", token.as_str(), token.line_col().0)))
+                _ => Result::Err(MarleaParserError::BadSpeciesCount { location: SourceSpan::from_pair(&token), text: token.as_str().to_string() })
             }            
             },
-            _ => Result::Err(MarleaParserError::ParseFailed(format!("found unexpected {} token {}, expected species count token", Self::rule_as_str(token.as_rule()), token.as_str()))),            
+            _ => Result::Err(Self::unexpected_token(&token, Rule::species_count)),            
+        }
+    }
+
+    /// parameter values may refer to any parameter declared above them
+    fn as_parameter (token: Pair<'_, Rule>, parameters: &HashMap<String, Parameter>) -> Result<(String, Parameter), MarleaParserError> {
+        match token.as_rule() {
+            Rule::parameter => {
+                let location = SourceSpan::from_pair(&token);
+                let mut possible_name = Option::None;
+                let mut possible_value = Option::None;
+
+                for sub_token in token.clone().into_inner() {
+                    match sub_token.as_rule() {
+                        Rule::parameter_name => possible_name = Some(sub_token.as_str().to_string()),
+                        Rule::expression => {
+                            possible_value = match Self::evaluate(sub_token, parameters) {
+                                Ok(value) => Some(value),
+                                Err(msg) => return Result::Err(msg)
+                            }
+                        },
+                        _ => ()
+                    }
+                }
+
+                match (possible_name, possible_value) {
+                    (Some(name), Some(value)) => Result::Ok((name, Parameter { value, location })),
+                    _ => Result::Err(MarleaParserError::BadParameter { location, text: token.as_str().to_string() })
+                }
+            },
+            _ => Result::Err(Self::unexpected_token(&token, Rule::parameter)),
+        }
+    }
+
+    /// look up the value of a parameter_name token
+    fn resolve_parameter (token: Pair<'_, Rule>, parameters: &HashMap<String, Parameter>) -> Result<Rational, MarleaParserError> {
+        match parameters.get(token.as_str()) {
+            Some(parameter) => Result::Ok(parameter.value),
+            None => Result::Err(MarleaParserError::UndefinedParameter { location: SourceSpan::from_pair(&token), name: token.as_str().to_string() }),
+        }
+    }
+
+    /// evaluate expression text which did not come from a csv row, such as a value in another format.
+    /// Returns None when the whole text is not an expression the csv grammar accepts
+    fn evaluate_text (text: &str, parameters: &HashMap<String, Parameter>) -> Option<Result<Rational, MarleaParserError>> {
+        match Self::parse(Rule::expression, text) {
+            Ok(mut token_stream) => match token_stream.next() {
+                Some(token) if token.as_str() == text => Some(Self::evaluate(token, parameters)),
+                _ => None,
+            },
+            Err(_) => None,
+        }
+    }
+
+    /// evaluate an expression token into an exact value
+    fn evaluate (token: Pair<'_, Rule>, parameters: &HashMap<String, Parameter>) -> Result<Rational, MarleaParserError> {
+        match token.as_rule() {
+            Rule::expression => {
+                EXPRESSION_PARSER.get_or_init(|| {
+                    PrattParser::new()
+                    .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::subtract, Assoc::Left))
+                    .op(Op::infix(Rule::multiply, Assoc::Left) | Op::infix(Rule::divide, Assoc::Left))
+                    .op(Op::prefix(Rule::negate))
+                    .op(Op::infix(Rule::power, Assoc::Right))
+                })
+                .map_primary(|operand| match operand.as_rule() {
+                    Rule::decimal => Rational::from_decimal(operand.as_str()).map_err(|err| Self::arithmetic_error(err, &operand)),
+                    Rule::parameter_name => Self::resolve_parameter(operand, parameters),
+                    Rule::expression => Self::evaluate(operand, parameters),
+                    _ => Result::Err(Self::unexpected_token(&operand, Rule::expression)),
+                })
+                .map_prefix(|operator, operand| {
+                    operand.and_then(|value| value.negate().map_err(|err| Self::arithmetic_error(err, &operator)))
+                })
+                .map_infix(|left, operator, right| {
+                    let (left, right) = match (left, right) {
+                        (Ok(left), Ok(right)) => (left, right),
+                        (Err(msg), _) | (_, Err(msg)) => return Result::Err(msg),
+                    };
+                    let value = match operator.as_rule() {
+                        Rule::add => left.add(right),
+                        Rule::subtract => left.subtract(right),
+                        Rule::multiply => left.multiply(right),
+                        Rule::divide => left.divide(right),
+                        Rule::power => left.power(right),
+                        _ => return Result::Err(Self::unexpected_token(&operator, Rule::expression)),
+                    };
+                    value.map_err(|err| Self::arithmetic_error(err, &operator))
+                })
+                .parse(token.into_inner())
+            },
+            _ => Result::Err(Self::unexpected_token(&token, Rule::expression)),
+        }
+    }
+
+    fn arithmetic_error (error: ArithmeticError, token: &Pair<'_, Rule>) -> MarleaParserError {
+        let location = SourceSpan::from_pair(token);
+        match error {
+            ArithmeticError::DivisionByZero => MarleaParserError::DivisionByZero { location },
+            ArithmeticError::Overflow => MarleaParserError::ArithmeticOverflow { location },
+            ArithmeticError::BadExponent => MarleaParserError::BadExponent { location },
+            // only decimal tokens underflow
+            ArithmeticError::Underflow => MarleaParserError::NonIntegral { location, text: token.as_str().to_string() },
+        }
+    }
+
+    fn unexpected_token (token: &Pair<'_, Rule>, expected: Rule) -> MarleaParserError {
+        MarleaParserError::UnexpectedToken { 
+            location: SourceSpan::from_pair(token), 
+            found: Self::rule_as_str(token.as_rule()), 
+            expected: Self::rule_as_str(expected), 
         }
     }
 
     pub fn rule_as_str(rule: Rule) -> &'static str {
         match rule {
             crate::Rule::coefficient => "coefficient",
+            crate::Rule::decimal => "decimal",
+            crate::Rule::include_path => "include_path",
+            crate::Rule::template_argument => "template_argument",
+            crate::Rule::instance => "instance",
+            crate::Rule::template_parameters => "template_parameters",
+            crate::Rule::template_statement => "template_statement",
+            crate::Rule::template_row => "template_row",
+            crate::Rule::template => "template",
+            crate::Rule::include => "include",
+            crate::Rule::add => "add",
+            crate::Rule::subtract => "subtract",
+            crate::Rule::multiply => "multiply",
+            crate::Rule::divide => "divide",
+            crate::Rule::power => "power",
+            crate::Rule::negate => "negate",
+            crate::Rule::binary_operator => "binary_operator",
+            crate::Rule::operand => "operand",
+            crate::Rule::expression => "expression",
+            crate::Rule::parameter => "parameter",
+            crate::Rule::parameter_name => "parameter_name",
             crate::Rule::comma_delimiter => "comma_delimiter", 
             crate::Rule::comment => "comment",
             crate::Rule::EOI => "end",
             crate::Rule::fat_arrow_delimiter => "fat_arrow_delimiter",
+            crate::Rule::double_arrow_delimiter => "double_arrow_delimiter",
             crate::Rule::name => "name",
             crate::Rule::new_line_delimiter => "new_line_delimiter",
             crate::Rule::plus_delimiter => "plus_delimiter",
@@ -248,7 +845,13 @@ impl CSVparser {
             crate::Rule::reactants => "reactants",
             crate::Rule::reaction => "reaction",
             crate::Rule::reaction_rate => "reaction_rate",
+            crate::Rule::reversible_reaction => "reversible_reaction",
+            crate::Rule::statement => "statement",
             crate::Rule::reaction_network => "reaction_network",
+            crate::Rule::row => "row",
+            crate::Rule::malformed_row => "malformed_row",
+            crate::Rule::recoverable_row => "recoverable_row",
+            crate::Rule::recovering_network => "recovering_network",
             crate::Rule::space_delimiter => "space_delimiter",
             crate::Rule::species_count => "species_count",
             crate::Rule::term => "term",
@@ -257,13 +860,6 @@ impl CSVparser {
 }
 
 
-#[derive(Debug, Clone)]
-pub enum MarleaParserError {
-    ParseFailed(String),
-    UnsupportedExt(String),
-    InvalidFile(String),
-}
-
 // object containing any settings needed or relevant to the marlea parser 
 pub struct MarleaParser;
 
@@ -280,41 +876,92 @@ impl MarleaParser {
                     Some("csv") => {
                         Self::handle_csv(path)
                     },
-                    Some(_) | None => Result::Err(MarleaParserError::UnsupportedExt(format!("provided file {} is not a supported format", path.display() ))),
+                    Some("xml") | Some("sbml") => {
+                        Self::handle_sbml(path)
+                    },
+                    Some("ant") => {
+                        Self::handle_antimony(path)
+                    },
+                    Some("net") => {
+                        Self::handle_bionetgen(path)
+                    },
+                    Some("json") => {
+                        Self::handle_document(path, DocumentFormat::Json)
+                    },
+                    Some("toml") => {
+                        Self::handle_document(path, DocumentFormat::Toml)
+                    },
+                    Some("yaml") | Some("yml") => {
+                        Self::handle_document(path, DocumentFormat::Yaml)
+                    },
+                    Some(_) | None => Result::Err(MarleaParserError::UnsupportedExt { path: path.to_path_buf() }),
                 }
             },
-            None => Result::Err(MarleaParserError::InvalidFile(format!("provided  Path: {} \ndid not contain an extension or does not exist", path.display() ))),
+            None => Result::Err(MarleaParserError::InvalidFile { path: path.to_path_buf() }),
         }
     }
 
+    /// Parses csv source text which did not come from a file,
+    /// `#include` rows have no directory to be resolved against and are reported as errors
+    pub fn parse_str(source: &str) -> Result<ReactionNetwork,MarleaParserError> {
+        CSVparser::as_reaction_network(source)
+    }
+
+    /// Parses csv source text which did not come from a file while skipping any malformed rows
+    pub fn parse_str_recovering(source: &str) -> (ReactionNetwork, Vec<MarleaParserError>) {
+        CSVparser::as_reaction_network_recovering(source)
+    }
+
+    /// Parses a reaction network recording which section each reaction and species count came from.
+    /// Sections are started by csv comment headers such as `,//Setup RXN Network,`, see [section],
+    /// other formats have no headers so all of their rows are in a single section without one
+    pub fn parse_sections(path: &Path) -> Result<SectionedNetwork,MarleaParserError> {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("csv") {
+            let reaction_network = Self::parse(path)?;
+            let mut section = Section::new(None);
+            section.reactions = reaction_network.get_reactions().iter().cloned().collect();
+            section.species_counts = reaction_network.get_solution().species_counts.iter().map(|(name, count)| (name.clone(), count.clone())).collect();
+            return Result::Ok(SectionedNetwork::new(reaction_network, vec![section]));
+        }
+
+        let mut loader = CsvLoader { include_stack: Vec::new(), recovering: false, tracking: Tracking { sections: true, rows: false }, diagnostics: Vec::new() };
+        let mut sectioned = loader.load(path)?.build_sectioned();
+        for header in sectioned.sections_mut().iter_mut().filter_map(|section| section.header.as_mut()) {
+            header.span.path = Some(path.to_path_buf());
+        }
+        Result::Ok(sectioned)
+    }
+
+    /// Parses csv source text which did not come from a file recording which section each row came from
+    pub fn parse_str_sections(source: &str) -> Result<SectionedNetwork,MarleaParserError> {
+        CSVparser::as_sectioned_network(source)
+    }
+
     /// figures out the encoding format based on the byte order mark and decodes it as such if true 
-    fn decode_file (bytes: &[u8]) -> Result<&str, &str> {
-
-        match bytes {
-            // Found UTF-8 byte order mark
-            [239, 187, 191, ..] => {
-                // Strip byte order mark from bytes
-                let sanitized_bytes = match bytes.strip_prefix(&[239, 187, 191]) {
-                    Some(text) => text,
-                    None => return Result::Err("matched prefix but could not find one to strip from bytes")
-                };
- 
-                match from_utf8(sanitized_bytes) {
-                    Ok(txt) => Result::Ok(txt),
-                    Err(_) => Result::Err(&"non valid utf8 input")
-                }
-            }, 
+    fn decode_file (bytes: &[u8]) -> Result<&str, MarleaParserError> {
+
+        let (offset, sanitized_bytes) = match bytes {
+            // Found UTF-8 byte order mark, strip it from bytes
+            [239, 187, 191, text @ ..] => (3, text),
             // Default to utf-8 encoding and assume no BOM was present
-            _ => {
-                match from_utf8(bytes) {
-                    Ok(txt) => Result::Ok(txt),
-                    Err(_) => Result::Err(&"non valid utf8 input")
-                }
+            _ => (0, bytes),
+        };
+
+        match from_utf8(sanitized_bytes) {
+            Ok(txt) => Result::Ok(txt),
+            Err(err) => {
+                // report the location of the first invalid byte using the text which did decode
+                let valid_text = from_utf8(&sanitized_bytes[..err.valid_up_to()]).unwrap_or_default();
+                let mut location = SourceSpan::from_offset(valid_text, valid_text.len());
+                location.start += offset;
+                location.end = location.start + err.error_len().unwrap_or(0);
+                Result::Err(MarleaParserError::Decode { location, source: err })
             }
         }
     }
     
-    fn handle_csv (path: &Path) -> Result<ReactionNetwork,MarleaParserError> { 
+    /// reads and decodes a source file into text 
+    pub fn read_source (path: &Path) -> Result<String,MarleaParserError> {
         // try to open the file 
         match File::open(path) {
             Ok(mut source_file) => {    
@@ -324,18 +971,145 @@ impl MarleaParser {
                 match source_file.read_to_end(&mut source_bytes) {
                     Ok(_) => {
                         // try to decode bytes from file
-                        let source_text = match Self::decode_file(&source_bytes) {
-                            Ok(txt) => txt,
-                            Err(msg) => return Result::Err(MarleaParserError::ParseFailed(msg.to_string())),
-                        };
+                        match Self::decode_file(&source_bytes) {
+                            Ok(txt) => Result::Ok(txt.to_string()),
+                            Err(err) => Result::Err(err.with_path(path)),
+                        }
+                    },
+                    Err(err) => Result::Err(MarleaParserError::Io { path: path.to_path_buf(), source: err }),
+                }
+            },
+            Err(err) => Result::Err(MarleaParserError::Io { path: path.to_path_buf(), source: err }),
+        }
+    }
 
-                        // parse using csv parser
-                        CSVparser::as_reaction_network(&source_text)
+    /// Parses a reaction network while skipping any malformed rows.
+    /// Returns the network built from the valid rows and a diagnostic for every problem found in the file,
+    /// only failing outright when the file itself cannot be read.
+    pub fn parse_recovering(path: &Path) -> Result<(ReactionNetwork, Vec<MarleaParserError>),MarleaParserError> {
+        match path.extension() {
+            Some(ext) => {
+                match ext.to_str() {
+                    Some("csv") => {
+                        let mut loader = CsvLoader { include_stack: Vec::new(), recovering: true, tracking: Tracking::default(), diagnostics: Vec::new() };
+                        let builder = loader.load(path)?;
+                        Result::Ok((builder.build(), loader.diagnostics))
+                    },
+                    Some("xml") | Some("sbml") => {
+                        let source_text = Self::read_source(path)?;
+                        let (reaction_network, diagnostics) = SBMLparser::as_reaction_network_recovering(&source_text);
+                        Result::Ok((reaction_network, diagnostics.into_iter().map(|err| err.with_path(path)).collect()))
+                    },
+                    Some("ant") => {
+                        let source_text = Self::read_source(path)?;
+                        let (reaction_network, diagnostics) = AntimonyParser::as_reaction_network_recovering(&source_text);
+                        Result::Ok((reaction_network, diagnostics.into_iter().map(|err| err.with_path(path)).collect()))
                     },
-                    Err(_) => Result::Err(MarleaParserError::ParseFailed(format!("failed to read {}" , path.display()))),
+                    Some("net") => {
+                        let source_text = Self::read_source(path)?;
+                        let (reaction_network, diagnostics) = BNGparser::as_reaction_network_recovering(&source_text);
+                        Result::Ok((reaction_network, diagnostics.into_iter().map(|err| err.with_path(path)).collect()))
+                    },
+                    Some(ext @ ("json" | "toml" | "yaml" | "yml")) => {
+                        let format = match ext {
+                            "json" => DocumentFormat::Json,
+                            "toml" => DocumentFormat::Toml,
+                            _ => DocumentFormat::Yaml,
+                        };
+                        let source_text = Self::read_source(path)?;
+                        let (reaction_network, diagnostics) = DocumentParser::as_reaction_network_recovering(format, &source_text);
+                        Result::Ok((reaction_network, diagnostics.into_iter().map(|err| err.with_path(path)).collect()))
+                    },
+                    Some(_) | None => Result::Err(MarleaParserError::UnsupportedExt { path: path.to_path_buf() }),
                 }
             },
-            Err(_) => Result::Err(MarleaParserError::ParseFailed(format!("failed to open {}" , path.display()))),
+            None => Result::Err(MarleaParserError::InvalidFile { path: path.to_path_buf() }),
+        }
+    }
+    
+    fn handle_csv (path: &Path) -> Result<ReactionNetwork,MarleaParserError> { 
+        let mut loader = CsvLoader { include_stack: Vec::new(), recovering: false, tracking: Tracking::default(), diagnostics: Vec::new() };
+
+        // parse using csv parser
+        loader.load(path).map(NetworkBuilder::build)
+    }
+
+    fn handle_sbml (path: &Path) -> Result<ReactionNetwork,MarleaParserError> {
+        let source_text = Self::read_source(path)?;
+
+        // parse using sbml parser
+        SBMLparser::as_reaction_network(&source_text).map_err(|err| err.with_path(path))
+    }
+
+    fn handle_antimony (path: &Path) -> Result<ReactionNetwork,MarleaParserError> {
+        let source_text = Self::read_source(path)?;
+
+        // parse using antimony parser
+        AntimonyParser::as_reaction_network(&source_text).map_err(|err| err.with_path(path))
+    }
+
+    fn handle_bionetgen (path: &Path) -> Result<ReactionNetwork,MarleaParserError> {
+        let source_text = Self::read_source(path)?;
+
+        // parse using bionetgen parser
+        BNGparser::as_reaction_network(&source_text).map_err(|err| err.with_path(path))
+    }
+
+    fn handle_document (path: &Path, format: DocumentFormat) -> Result<ReactionNetwork,MarleaParserError> {
+        let source_text = Self::read_source(path)?;
+
+        // parse json, toml or yaml using the shared document schema
+        DocumentParser::as_reaction_network(format, &source_text).map_err(|err| err.with_path(path))
+    }
+}
+
+/// loads a csv file along with every file it includes
+struct CsvLoader {
+    // canonical paths of the files currently being loaded, outermost first
+    include_stack: Vec<PathBuf>,
+    // skip bad rows collecting diagnostics instead of stopping at the first error
+    recovering: bool,
+    // what each builder records beyond the network
+    tracking: Tracking,
+    diagnostics: Vec<MarleaParserError>,
+}
+
+impl CsvLoader {
+    fn load (&mut self, path: &Path) -> Result<NetworkBuilder,MarleaParserError> {
+        let source_text = MarleaParser::read_source(path)?;
+        let canonical_path = match path.canonicalize() {
+            Ok(canonical_path) => canonical_path,
+            Err(err) => return Result::Err(MarleaParserError::Io { path: path.to_path_buf(), source: err }),
+        };
+        // includes are resolved relative to the directory of the including file
+        let directory = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        self.include_stack.push(canonical_path);
+        let result = if self.recovering {
+            let (builder, diagnostics) = CSVparser::interpret_network_recovering(&source_text, &mut |include, location| self.load_include(&directory, include, location));
+            self.diagnostics.extend(diagnostics.into_iter().map(|err| err.with_path(path)));
+            Result::Ok(builder)
+        } else {
+            CSVparser::interpret_network(&source_text, self.tracking, &mut |include, location| self.load_include(&directory, include, location))
+            .map(|mut builder| {
+                builder.set_path(path);
+                builder
+            })
+            .map_err(|err| err.with_path(path))
+        };
+        self.include_stack.pop();
+
+        result
+    }
+
+    fn load_include (&mut self, directory: &Path, include: &str, location: &SourceSpan) -> Result<NetworkBuilder,MarleaParserError> {
+        let path = directory.join(include);
+        match path.canonicalize() {
+            Ok(canonical_path) if self.include_stack.contains(&canonical_path) => {
+                Result::Err(MarleaParserError::IncludeCycle { location: location.clone(), path })
+            },
+            Ok(_) => self.load(&path),
+            Err(err) => Result::Err(MarleaParserError::MissingInclude { location: location.clone(), path, source: err }),
         }
     }
 }
@@ -490,13 +1264,8 @@ mod tests {
             match MarleaParser::parse(path) {
                 Ok(reaction_network) => reaction_network,
                 Err(msg) => {
-                    match msg {
-                        MarleaParserError::ParseFailed(text) => {
-                            print!("{}\n\n", text);
-                            panic!("(;-;)");
-                        },
-                        _ => panic!("huh?")
-                    }
+                    print!("{}\n\n", msg);
+                    panic!("(;-;)");
                 },
             }
         )
@@ -513,4 +1282,223 @@ mod tests {
         }
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn csv_parser_reports_error_location() {
+        let input = "A + B => C,10,\nA + => C,10,\n";
+
+        match CSVparser::as_reaction_network(input) {
+            Err(err @ MarleaParserError::Syntax { .. }) => {
+                assert_eq!(err.location().map(|location| location.line), Some(2));
+                assert!(std::error::Error::source(&err).is_some());
+            },
+            other => panic!("expected syntax error got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn csv_parser_reports_bad_coefficient() {
+        let input = "A => B,1,\n99999999999999999999999 A => B,1,\n";
+
+        match CSVparser::as_reaction_network(input) {
+            Err(MarleaParserError::BadCoefficient { location, text }) => {
+                assert_eq!(text, "99999999999999999999999");
+                assert_eq!((location.line, location.column), (2, 1));
+                assert_eq!(&input[location.start..location.end], text);
+            },
+            other => panic!("expected bad coefficient got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn csv_parser_recovers_from_bad_rows() {
+        let input = ",//setup,\nA + B => C,10,\nA + => C,10,\nC => D,\nD,5,\n99999999999999999999999 D => E,1,\nE => F,1,\n";
+
+        let (reaction_network, diagnostics) = CSVparser::as_reaction_network_recovering(input);
+        let lines: Vec<usize> = diagnostics.iter().filter_map(|err| err.location()).map(|location| location.line).collect();
+        assert_eq!(lines, vec![3, 4, 6]);
+        assert!(matches!(diagnostics[0], MarleaParserError::Syntax { .. }));
+        assert!(matches!(diagnostics[2], MarleaParserError::BadCoefficient { .. }));
+        let expected = CSVparser::as_reaction_network("A + B => C,10,\nD,5,\nE => F,1,\n").unwrap();
+        assert_eq!(reaction_network.get_reactions(), expected.get_reactions());
+        assert_eq!(reaction_network.get_solution().species_counts, expected.get_solution().species_counts);
+    }
+
+    #[test]
+    fn csv_parser_expands_reversible_reactions() {
+        let input = "A + B <=> C, 10, 1,\nNULL<=>D,5,2,\nC => E,3,\n";
+
+        let reversible = CSVparser::as_reaction_network(input).unwrap();
+        let expanded = CSVparser::as_reaction_network("A + B => C,10,\nC => A + B,1,\nNULL => D,5,\nD => NULL,2,\nC => E,3,\n").unwrap();
+        assert_eq!(reversible.get_reactions().len(), 5);
+        assert_eq!(reversible.get_reactions(), expanded.get_reactions());
+        assert_eq!(reversible.get_solution().species_counts, expanded.get_solution().species_counts);
+
+        // a reversible reaction needs both rates
+        assert!(CSVparser::as_reaction_network("A <=> B, 10,\n").is_err());
+    }
+
+    #[test]
+    fn csv_parser_accepts_decimal_rates() {
+        let decimal = CSVparser::as_reaction_network("A => B,1e4,\nB => C,2.5E1,\nC => A,3.0,\n").unwrap();
+        let integer = CSVparser::as_reaction_network("A => B,10000,\nB => C,25,\nC => A,3,\n").unwrap();
+        assert_eq!(decimal.get_reactions(), integer.get_reactions());
+
+        assert!(matches!(CSVparser::as_reaction_network("A => B,0.5,\n"), Err(MarleaParserError::InexactRate { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("A => B,1e-3,\n"), Err(MarleaParserError::InexactRate { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("A => B,1e-100,\n"), Err(MarleaParserError::InexactRate { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("A => B,1e-39 / 1e-40,\n"), Err(MarleaParserError::InexactRate { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("A => B,1 + 1e-39 - 1e-40,\n"), Err(MarleaParserError::InexactRate { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("A,1e-45 / 1e-50,\n"), Err(MarleaParserError::NonIntegral { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("(1e-40) A => B,1,\n"), Err(MarleaParserError::NonIntegral { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("A => B,0.0,\n"), Err(MarleaParserError::ZeroRate { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("A => B,1e30,\n"), Err(MarleaParserError::BadRate { .. })));
+    }
+
+    #[test]
+    fn csv_parser_resolves_parameters() {
+        let input = "A + n B => C,fast,\n#param fast = 1e4\n#param n = 2,\nC <=> D,fast,slow,\n#param slow=1\nA,n,\n";
+
+        let parameterized = CSVparser::as_reaction_network(input).unwrap();
+        let literal = CSVparser::as_reaction_network("A + 2 B => C,10000,\nC <=> D,10000,1,\nA,2,\n").unwrap();
+        assert_eq!(parameterized.get_reactions(), literal.get_reactions());
+        assert_eq!(parameterized.get_solution().species_counts, literal.get_solution().species_counts);
+
+        match CSVparser::as_reaction_network("A => B,fats,\n#param fast = 10\n") {
+            Err(MarleaParserError::UndefinedParameter { name, location }) => {
+                assert_eq!(name, "fats");
+                assert_eq!((location.line, location.column), (1, 8));
+            },
+            other => panic!("expected undefined parameter got {:?}", other),
+        }
+        match CSVparser::as_reaction_network("#param fast = 10\nA => B,fast,\n#param fast = 100\n") {
+            Err(MarleaParserError::DuplicateParameter { name, location, previous }) => {
+                assert_eq!(name, "fast");
+                assert_eq!((location.line, previous.line), (3, 1));
+            },
+            other => panic!("expected duplicate parameter got {:?}", other),
+        }
+        assert!(matches!(CSVparser::as_reaction_network("#param half = 0.5\nA,half,\n"), Err(MarleaParserError::NonIntegral { .. })));
+    }
+
+    #[test]
+    fn csv_parser_evaluates_expressions() {
+        let input = "#param fast = 1e4\n#param n = 7\nA + (n - 5) B => C,fast * 10,\nC <=> D,2^8,(n + 1) / 2,\nA,-2 + 3 * 4,\n#param half = 0.5\nB,half * 4,\n";
+
+        let evaluated = CSVparser::as_reaction_network(input).unwrap();
+        let literal = CSVparser::as_reaction_network("A + 2 B => C,100000,\nC <=> D,256,4,\nA,10,\nB,2,\n").unwrap();
+        assert_eq!(evaluated.get_reactions(), literal.get_reactions());
+        assert_eq!(evaluated.get_solution().species_counts, literal.get_solution().species_counts);
+
+        match CSVparser::as_reaction_network("A => B,10,\nA => C,10 / (2 - 2),\n") {
+            Err(MarleaParserError::DivisionByZero { location }) => assert_eq!((location.line, location.column), (2, 11)),
+            other => panic!("expected division by zero got {:?}", other),
+        }
+        assert!(matches!(CSVparser::as_reaction_network("A => B,10 / 4,\n"), Err(MarleaParserError::InexactRate { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("(3 / 2) A => B,1,\n"), Err(MarleaParserError::NonIntegral { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("A,1 - 2,\n"), Err(MarleaParserError::BadSpeciesCount { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("A => B,10^100,\n"), Err(MarleaParserError::ArithmeticOverflow { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("A => B,2^0.5,\n"), Err(MarleaParserError::BadExponent { .. })));
+        assert!(matches!(CSVparser::as_reaction_network("#param a = b + 1\n#param b = 1\n"), Err(MarleaParserError::UndefinedParameter { .. })));
+    }
+
+    #[test]
+    fn csv_parser_expands_templates() {
+        let input = concat!(
+            "#param fast = 10000\n",
+            "#template not(X),//not gate\n",
+            "2 X.not.index.0 => X.not.index.1,fast,\n",
+            "2 X.not.index.1 => X.not.index.1,fast,\n",
+            "destruct => destruct + X.not.index.0,1,\n",
+            "X + X.not.index.0 => X,fast,\n",
+            "X + X.not.index.1 => X,fast,\n",
+            "#end\n",
+            "#template both_not(X, Y)\n",
+            "#use not(X)\n",
+            "#use not(Y)\n",
+            "X.not.index.1 + Y.not.index.1 => X.Y.done,fast,\n",
+            "#end\n",
+            "#use both_not(setup.call, current_value)\n",
+        );
+        let literal = concat!(
+            "2 setup.call.not.index.0 => setup.call.not.index.1,10000,\n2 setup.call.not.index.1 => setup.call.not.index.1,10000,\n",
+            "destruct => destruct + setup.call.not.index.0,1,\nsetup.call + setup.call.not.index.0 => setup.call,10000,\n",
+            "setup.call + setup.call.not.index.1 => setup.call,10000,\n",
+            "2 current_value.not.index.0 => current_value.not.index.1,10000,\n2 current_value.not.index.1 => current_value.not.index.1,10000,\n",
+            "destruct => destruct + current_value.not.index.0,1,\ncurrent_value + current_value.not.index.0 => current_value,10000,\n",
+            "current_value + current_value.not.index.1 => current_value,10000,\n",
+            "setup.call.not.index.1 + current_value.not.index.1 => setup.call.current_value.done,10000,\n",
+        );
+
+        let expanded = CSVparser::as_reaction_network(input).unwrap();
+        let literal = CSVparser::as_reaction_network(literal).unwrap();
+        assert_eq!(expanded.get_reactions(), literal.get_reactions());
+        assert_eq!(expanded.get_solution().species_counts, literal.get_solution().species_counts);
+
+        match CSVparser::as_reaction_network("#use not(A)\n#template not(X)\nX => NULL,1,\n#end\n") {
+            Err(MarleaParserError::UndefinedTemplate { name, location }) => {
+                assert_eq!(name, "not");
+                assert_eq!(location.line, 1);
+            },
+            other => panic!("expected undefined template got {:?}", other),
+        }
+        assert!(matches!(
+            CSVparser::as_reaction_network("#template not(X)\nX => NULL,1,\n#end\n#use not(A, B)\n"),
+            Err(MarleaParserError::TemplateArguments { expected: 1, found: 2, .. })
+        ));
+        assert!(matches!(
+            CSVparser::as_reaction_network("#template not(X)\n#end\n#template not(Y)\n#end\n"),
+            Err(MarleaParserError::DuplicateTemplate { .. })
+        ));
+
+        // a bad row inside a template is reported where it sits in the file
+        let (_, diagnostics) = CSVparser::as_reaction_network_recovering("#template not(X)\nX => NULL,1,\nX => => Y,1,\n#end\nA => B,1,\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location().map(|location| location.line), Some(3));
+    }
+
+    #[test]
+    fn marlea_parser_reports_io_error() {
+        let path = Path::new("test_data").join("does_not_exist.csv");
+
+        match MarleaParser::parse(&path) {
+            Err(err @ MarleaParserError::Io { .. }) => {
+                assert_eq!(err.path(), Some(path.as_path()));
+                assert!(std::error::Error::source(&err).is_some());
+            },
+            other => panic!("expected io error got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn marlea_parser_resolves_includes() {
+        let directory = Path::new("test_data").join("include");
+
+        let included = MarleaParser::parse(&directory.join("network.csv")).unwrap();
+        let literal = MarleaParser::parse_str(concat!(
+            "2 input.not.index.0 => input.not.index.1,10000,\n2 input.not.index.1 => input.not.index.1,10000,\n",
+            "input + input.not.index.0 => input,10000,\ninput + input.not.index.1 => input,10000,\n",
+            "destruct => destruct + input.not.index.0,1,\ninput,1,\n",
+        )).unwrap();
+        assert_eq!(included.get_reactions(), literal.get_reactions());
+        assert_eq!(included.get_solution().species_counts, literal.get_solution().species_counts);
+
+        match MarleaParser::parse(&directory.join("cycle_a.csv")) {
+            Err(MarleaParserError::IncludeCycle { location, path }) => {
+                assert_eq!(location.path, Some(directory.join("cycle_b.csv")));
+                assert_eq!(location.line, 2);
+                assert_eq!(path, directory.join("cycle_a.csv"));
+            },
+            other => panic!("expected include cycle got {:?}", other),
+        }
+
+        // each diagnostic names the file it came from
+        let (_, diagnostics) = MarleaParser::parse_recovering(&directory.join("bad_include.csv")).unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].path(), Some(directory.join("circuits").join("broken.csv").as_path()));
+        assert_eq!(diagnostics[0].location().map(|location| location.line), Some(2));
+        assert!(matches!(&diagnostics[1], MarleaParserError::MissingInclude { location, .. } if location.path == Some(directory.join("bad_include.csv")) && location.line == 3));
+
+        assert!(matches!(MarleaParser::parse_str("#include \"network.csv\"\n"), Err(MarleaParserError::UnresolvedInclude { .. })));
+    }
+}
@@ -0,0 +1,418 @@
+//! Checks csv networks for rows which parse but are probably mistakes.
+//!
+//! A network holds each reaction once and each species has a single initial count, so when a file says more than that
+//! the parser quietly keeps one. Linting reports these instead, each finding pointing at both rows involved.
+//!
+//! ```text
+//! A => B,10,         duplicate reaction, the network only holds it once
+//! A => B,10,
+//! A => B,20,         conflicting rate, both reactions are kept and occur independently
+//! A,5,
+//! A,7,               duplicate species count, only the last count is used
+//! ```
+//!
+//! A typo in a species name quietly creates a new species starting at zero. [Linter::strict] also reports species which
+//! are consumed but never produced and start at zero, so the reactions consuming them can never occur,
+//! species which are produced but never consumed or observed, and names which are a single edit away from a more common one.
+//!
+//! ```text
+//! next_value + fibonacci.call => next_value,1,
+//! next_vlaue => NULL,1,                   dead species and near miss, did you mean `next_value`?
+//! ```
+//!
+//! Each kind of finding has a [Severity] so a project can ignore it, be warned about it or treat it as an error.
+//! Duplicates are reported first, then dead and unused species in the order they are first mentioned, then near misses.
+//! Only csv files are linted, other formats are parsed as usual without any findings.
+
+use std::{collections::{HashMap, HashSet, hash_map::Entry}, path::Path};
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, reaction::term::Term, solution::{Count, Name}};
+
+use crate::{Added, CSVparser, CsvLoader, MarleaParser, MarleaParserError, NetworkBuilder, SourceSpan, Tracking};
+
+/// How a kind of finding is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// not reported
+    Allow,
+    /// returned alongside the network
+    Warn,
+    /// returned as an error instead of the network
+    Deny,
+}
+
+/// Parses a network and checks it for likely mistakes, see the [module documentation](self)
+#[derive(Debug, Clone)]
+pub struct Linter {
+    duplicates: Severity,
+    dead_species: Severity,
+    unused_species: Severity,
+    near_misses: Severity,
+    // species read as the output of a simulation which are never reported as unused
+    observed: Vec<String>,
+}
+
+/// the species and coefficients of one side of a reaction in a fixed order
+type Side = Vec<(String, u64)>;
+
+/// where a species is mentioned by the rows of a network
+struct Usage<'a> {
+    first: &'a SourceSpan,
+    consumed: Option<&'a SourceSpan>,
+    produced: Option<&'a SourceSpan>,
+    mentions: usize,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new()
+    }
+}
+
+impl Linter {
+    /// a linter which warns about duplicates, the species checks are opt in
+    pub fn new() -> Self {
+        Linter {
+            duplicates: Severity::Warn,
+            dead_species: Severity::Allow,
+            unused_species: Severity::Allow,
+            near_misses: Severity::Allow,
+            observed: Vec::new(),
+        }
+    }
+
+    /// a linter which rejects a network with any finding
+    pub fn strict() -> Self {
+        Linter {
+            duplicates: Severity::Deny,
+            dead_species: Severity::Deny,
+            unused_species: Severity::Deny,
+            near_misses: Severity::Deny,
+            observed: Vec::new(),
+        }
+    }
+
+    /// severity of reactions written more than once, reactions written again with a different rate
+    /// and species whose initial count is set more than once
+    pub fn duplicates(mut self, severity: Severity) -> Self {
+        self.duplicates = severity;
+        self
+    }
+
+    /// severity of species which are consumed by a reaction but never produced by one and have no initial count
+    pub fn dead_species(mut self, severity: Severity) -> Self {
+        self.dead_species = severity;
+        self
+    }
+
+    /// severity of species which are produced by a reaction but never consumed by one and are not observed
+    pub fn unused_species(mut self, severity: Severity) -> Self {
+        self.unused_species = severity;
+        self
+    }
+
+    /// severity of names which differ from a more common name by a single edit,
+    /// ie `next_vlaue` when `next_value` is used throughout the rest of the network
+    pub fn near_misses(mut self, severity: Severity) -> Self {
+        self.near_misses = severity;
+        self
+    }
+
+    /// species which are outputs of the network, such as the result of a calculation, so are never reported as unused
+    pub fn observe(mut self, names: &[&str]) -> Self {
+        self.observed.extend(names.iter().map(|name| name.to_string()));
+        self
+    }
+
+    /// Parses a network file and lints it.
+    /// Returns the network along with a warning for each finding, or the first denied finding as an error
+    pub fn lint(&self, path: &Path) -> Result<(ReactionNetwork, Vec<MarleaParserError>), MarleaParserError> {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("csv") {
+            return MarleaParser::parse(path).map(|reaction_network| (reaction_network, Vec::new()));
+        }
+
+        let mut loader = CsvLoader { include_stack: Vec::new(), recovering: false, tracking: Tracking { sections: false, rows: true }, diagnostics: Vec::new() };
+        self.finish(loader.load(path)?)
+    }
+
+    /// Parses csv source text which did not come from a file and lints it
+    pub fn lint_str(&self, source: &str) -> Result<(ReactionNetwork, Vec<MarleaParserError>), MarleaParserError> {
+        self.finish(CSVparser::interpret_network(source, Tracking { sections: false, rows: true }, &mut CSVparser::unresolved_include)?)
+    }
+
+    fn finish(&self, mut builder: NetworkBuilder) -> Result<(ReactionNetwork, Vec<MarleaParserError>), MarleaParserError> {
+        let rows = builder.rows.take().unwrap_or_default();
+        let mut findings = self.duplicate_findings(&rows);
+        findings.extend(self.species_findings(&rows, &builder.species_counts));
+
+        let mut warnings = Vec::new();
+        for (severity, finding) in findings {
+            match severity {
+                Severity::Allow => (),
+                Severity::Warn => warnings.push(finding),
+                Severity::Deny => return Result::Err(finding),
+            }
+        }
+        Result::Ok((builder.build(), warnings))
+    }
+
+    /// compare every row with the rows above it, findings are in the order of the later row
+    fn duplicate_findings(&self, rows: &[(Added, SourceSpan)]) -> Vec<(Severity, MarleaParserError)> {
+        let mut reactions: HashMap<(Side, Side), (u64, &SourceSpan)> = HashMap::new();
+        let mut species_counts: HashMap<&Name, &SourceSpan> = HashMap::new();
+        let mut findings = Vec::new();
+
+        for (added, location) in rows {
+            match added {
+                Added::Reaction(reaction) => {
+                    let rate = reaction.get_reaction_rate();
+                    match reactions.entry((Self::as_side(reaction.get_reactants()), Self::as_side(reaction.get_products()))) {
+                        // compare against the first time a reaction was written so every repeat points at the same row
+                        Entry::Occupied(first) => {
+                            let (previous_rate, previous) = *first.get();
+                            let previous = Box::new(previous.clone());
+                            findings.push(match previous_rate == rate {
+                                true => MarleaParserError::DuplicateReaction { location: location.clone(), previous },
+                                false => MarleaParserError::ConflictingRate { location: location.clone(), rate, previous_rate, previous },
+                            });
+                        },
+                        Entry::Vacant(vacant) => {
+                            vacant.insert((rate, location));
+                        },
+                    }
+                },
+                Added::SpeciesCount(name) => {
+                    // compare against the count being replaced
+                    if let Some(previous) = species_counts.insert(name, location) {
+                        findings.push(MarleaParserError::DuplicateSpeciesCount { location: location.clone(), name: name.0.clone(), previous: Box::new(previous.clone()) });
+                    }
+                },
+            }
+        }
+
+        findings.into_iter().map(|finding| (self.duplicates, finding)).collect()
+    }
+
+    /// dead and unused species in the order they are first mentioned followed by near miss names
+    fn species_findings(&self, rows: &[(Added, SourceSpan)], species_counts: &HashMap<Name, Count>) -> Vec<(Severity, MarleaParserError)> {
+        let usages = Self::as_usages(rows);
+        let mut findings = Vec::new();
+
+        for (name, usage) in &usages {
+            match (usage.consumed, usage.produced) {
+                (Some(location), None) if species_counts.get(&Name(name.to_string())).is_none_or(|count| count.0 == 0) => {
+                    findings.push((self.dead_species, MarleaParserError::DeadSpecies { location: location.clone(), name: name.to_string() }));
+                },
+                (None, Some(location)) if !self.observed.iter().any(|observed| observed == name) => {
+                    findings.push((self.unused_species, MarleaParserError::UnusedSpecies { location: location.clone(), name: name.to_string() }));
+                },
+                _ => (),
+            }
+        }
+
+        // the less used name of a pair is the likely typo, or the later one if both are used as often
+        let mut near_misses = Vec::new();
+        let mut flagged = HashSet::new();
+        for (position, (name, usage)) in usages.iter().enumerate() {
+            for (other_name, other_usage) in &usages[position + 1..] {
+                if !Self::is_near_miss(name, other_name) {
+                    continue;
+                }
+                let (typo, suggestion) = match usage.mentions > other_usage.mentions {
+                    true => ((other_name, other_usage), name),
+                    false => ((name, usage), other_name),
+                };
+                if flagged.insert(*typo.0) {
+                    near_misses.push((typo.1.first, MarleaParserError::NearMissName { location: typo.1.first.clone(), name: typo.0.to_string(), suggestion: suggestion.to_string() }));
+                }
+            }
+        }
+        near_misses.sort_by_key(|(first, _)| (first.path.clone(), first.start));
+        findings.extend(near_misses.into_iter().map(|(_, finding)| (self.near_misses, finding)));
+
+        findings
+    }
+
+    /// every species mentioned by the rows in the order they are first mentioned
+    fn as_usages<'a>(rows: &'a [(Added, SourceSpan)]) -> Vec<(&'a str, Usage<'a>)> {
+        let mut usages: Vec<(&str, Usage)> = Vec::new();
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        let mut mention = |name: &'a Name, location: &'a SourceSpan| -> usize {
+            let position = *positions.entry(name.0.as_str()).or_insert_with(|| {
+                usages.push((name.0.as_str(), Usage { first: location, consumed: None, produced: None, mentions: 0 }));
+                usages.len() - 1
+            });
+            usages[position].1.mentions += 1;
+            position
+        };
+
+        let mut consumed = Vec::new();
+        let mut produced = Vec::new();
+        for (added, location) in rows {
+            match added {
+                Added::Reaction(reaction) => {
+                    for term in reaction.get_reactants() {
+                        consumed.push((mention(term.get_species_name(), location), location));
+                    }
+                    for term in reaction.get_products() {
+                        produced.push((mention(term.get_species_name(), location), location));
+                    }
+                },
+                Added::SpeciesCount(name) => {
+                    mention(name, location);
+                },
+            }
+        }
+
+        for (position, location) in consumed {
+            usages[position].1.consumed.get_or_insert(location);
+        }
+        for (position, location) in produced {
+            usages[position].1.produced.get_or_insert(location);
+        }
+        usages
+    }
+
+    /// whether two names differ by a single edit to one of their dot separated segments,
+    /// segments shorter than four characters or differing only in digits are not compared so `A` and `B`
+    /// or `index.0` and `index.1` are not near misses
+    fn is_near_miss(name: &str, other_name: &str) -> bool {
+        let segments = name.split('.').collect::<Vec<&str>>();
+        let other_segments = other_name.split('.').collect::<Vec<&str>>();
+        if segments.len() != other_segments.len() {
+            return false;
+        }
+
+        let differing = segments.into_iter().zip(other_segments).filter(|(segment, other_segment)| segment != other_segment).collect::<Vec<(&str, &str)>>();
+        match differing.as_slice() {
+            [(segment, other_segment)] => {
+                let without_digits = |text: &str| text.chars().filter(|character| !character.is_ascii_digit()).collect::<String>();
+                segment.chars().count() >= 4
+                && other_segment.chars().count() >= 4
+                && without_digits(segment) != without_digits(other_segment)
+                && Self::edit_distance(segment, other_segment) <= 1
+            },
+            _ => false,
+        }
+    }
+
+    /// optimal string alignment distance, inserting, deleting or replacing a character
+    /// or swapping two adjacent characters each count as one edit
+    fn edit_distance(text: &str, other_text: &str) -> usize {
+        let (text, other_text) = (text.chars().collect::<Vec<char>>(), other_text.chars().collect::<Vec<char>>());
+        let mut distances = vec![vec![0; other_text.len() + 1]; text.len() + 1];
+        for (i, row) in distances.iter_mut().enumerate() {
+            row[0] = i;
+        }
+        for (j, distance) in distances[0].iter_mut().enumerate() {
+            *distance = j;
+        }
+
+        for i in 1..=text.len() {
+            for j in 1..=other_text.len() {
+                let replace = usize::from(text[i - 1] != other_text[j - 1]);
+                let mut distance = (distances[i - 1][j] + 1).min(distances[i][j - 1] + 1).min(distances[i - 1][j - 1] + replace);
+                if i > 1 && j > 1 && text[i - 1] == other_text[j - 2] && text[i - 2] == other_text[j - 1] {
+                    distance = distance.min(distances[i - 2][j - 2] + 1);
+                }
+                distances[i][j] = distance;
+            }
+        }
+        distances[text.len()][other_text.len()]
+    }
+
+    /// terms are compared regardless of the order they were written in
+    fn as_side(terms: &[Term]) -> Side {
+        let mut side = terms.iter()
+            .map(|term| (term.get_species_name().0.clone(), term.get_coefficient().0))
+            .collect::<Side>();
+        side.sort_unstable();
+        side
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{MarleaParserError, lint::{Linter, Severity}};
+
+    #[test]
+    fn linter_reports_duplicates_at_both_rows() {
+        let source = concat!(
+            "A + B => C,10,\n",
+            "B + A => C,10,\n",
+            "A <=> C,1,2,\n",
+            "A + B => C,20,\n",
+            "A,5,\n",
+            "A,7,\n",
+        );
+        let (network, warnings) = Linter::new().lint_str(source).unwrap();
+        assert_eq!(network.get_reactions().len(), 5);
+        assert_eq!(warnings.len(), 3);
+
+        match &warnings[0] {
+            MarleaParserError::DuplicateReaction { location, previous } => assert_eq!((location.line, previous.line), (2, 1)),
+            other => panic!("expected a duplicate reaction, found {:?}", other),
+        }
+        match &warnings[1] {
+            MarleaParserError::ConflictingRate { location, rate, previous_rate, previous } => assert_eq!((location.line, *rate, *previous_rate, previous.line), (4, 20, 10, 1)),
+            other => panic!("expected a conflicting rate, found {:?}", other),
+        }
+        match &warnings[2] {
+            MarleaParserError::DuplicateSpeciesCount { location, name, previous } => assert_eq!((location.line, name.as_str(), previous.line), (6, "A", 5)),
+            other => panic!("expected a duplicate species count, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn linter_severity_is_configurable() {
+        let source = "A => B,1,\nA => B,1,\n";
+        assert!(Linter::new().duplicates(Severity::Allow).lint_str(source).unwrap().1.is_empty());
+        assert!(matches!(Linter::new().duplicates(Severity::Deny).lint_str(source), Err(MarleaParserError::DuplicateReaction { .. })));
+        assert!(Linter::new().duplicates(Severity::Deny).lint_str("A => B,1,\nB => A,1,\nA,1,\n").is_ok());
+    }
+
+    #[test]
+    fn linter_follows_fibonacci_file() {
+        // the fibonacci calculator writes several of its not gate reactions twice
+        let path = Path::new("test_data").join("Fibonacci_calculator.csv");
+        let (_, warnings) = Linter::new().lint(&path).unwrap();
+        assert!(!warnings.is_empty());
+        assert!(warnings.iter().all(|warning| matches!(warning, MarleaParserError::DuplicateReaction { .. }) && warning.path() == Some(path.as_path())));
+    }
+
+    #[test]
+    fn strict_linter_reports_dead_unused_and_misspelled_species() {
+        let source = concat!(
+            "next_value + fibonacci.call => next_value + result,1,\n",
+            "next_value => NULL,1,\n",
+            "next_vlaue => NULL,1,\n",
+            "next_value,3,\n",
+            "fibonacci.call,1,\n",
+        );
+        let linter = Linter::new().dead_species(Severity::Warn).unused_species(Severity::Warn).near_misses(Severity::Warn);
+        let (_, warnings) = linter.lint_str(source).unwrap();
+        assert_eq!(warnings.len(), 3);
+        assert!(matches!(&warnings[0], MarleaParserError::UnusedSpecies { location, name } if location.line == 1 && name == "result"));
+        assert!(matches!(&warnings[1], MarleaParserError::DeadSpecies { location, name } if location.line == 3 && name == "next_vlaue"));
+        assert!(matches!(&warnings[2], MarleaParserError::NearMissName { location, name, suggestion } if location.line == 3 && name == "next_vlaue" && suggestion == "next_value"));
+
+        // outputs of the network are not unused
+        assert_eq!(linter.observe(&["result"]).lint_str(source).unwrap().1.len(), 2);
+        assert!(matches!(Linter::strict().observe(&["result"]).lint_str(source), Err(MarleaParserError::DeadSpecies { .. })));
+        assert!(Linter::new().lint_str(source).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn near_misses_ignore_short_and_numbered_names() {
+        assert!(Linter::is_near_miss("destruct", "destrcut"));
+        assert!(Linter::is_near_miss("setup.call", "setup.calls"));
+        assert!(!Linter::is_near_miss("A", "B"));
+        assert!(!Linter::is_near_miss("next_value.less_than.2.index.0", "next_value.less_than.2.index.1"));
+        assert!(!Linter::is_near_miss("value1", "value2"));
+        assert!(!Linter::is_near_miss("setup.call", "setup.call.not"));
+        assert_eq!(Linter::edit_distance("kitten", "sitting"), 3);
+        assert_eq!(Linter::edit_distance("next_vlaue", "next_value"), 1);
+    }
+}
@@ -0,0 +1,56 @@
+//! Recognises mass action rate laws shared by the SBML and Antimony importers.
+//!
+//! A mass action law is a rate constant multiplied by each reactant raised to its coefficient, such as `k * A * B^2`.
+//! Each importer flattens a law from its own syntax tree into factors and their powers,
+//! then feeds them to a [MassAction] which multiplies the constants and checks the species orders against the reactants.
+
+use std::collections::HashMap;
+
+use marlea_engine::trial::reaction_network::reaction::term::Term;
+
+use crate::number::{ArithmeticError, Rational};
+
+/// The rate constant and species orders of a product of factors
+pub(crate) struct MassAction<'a> {
+    rate: Rational,
+    // total power each species is raised to, None once a total is too large for a u64
+    orders: Option<HashMap<&'a str, u64>>,
+}
+
+impl<'a> MassAction<'a> {
+    pub(crate) fn new() -> Self {
+        MassAction { rate: Rational::from_integer(1), orders: Some(HashMap::new()) }
+    }
+
+    /// multiply by a species raised to a power, an order too large for a u64 can never match a reaction's coefficients
+    pub(crate) fn species(&mut self, name: &'a str, power: u64) {
+        if let Some(orders) = &mut self.orders {
+            let order = orders.entry(name).or_insert(0);
+            match order.checked_add(power) {
+                Some(total) => *order = total,
+                None => self.orders = None,
+            }
+        }
+    }
+
+    /// multiply the rate constant by a value raised to a power
+    pub(crate) fn constant(&mut self, value: Rational, power: u64) -> Result<(), ArithmeticError> {
+        self.rate = value.power(Rational::from_integer(power)).and_then(|value| self.rate.multiply(value))?;
+        Ok(())
+    }
+
+    /// the rate constant if the species orders are exactly the coefficients of the given species, None otherwise
+    pub(crate) fn rate_over(&self, species: &[Term]) -> Option<Rational> {
+        let orders = self.orders.as_ref()?;
+        let mut expected: HashMap<&str, u64> = HashMap::new();
+        for term in species {
+            let coefficient = expected.entry(term.get_species_name().0.as_str()).or_insert(0);
+            *coefficient = coefficient.checked_add(term.get_coefficient().0)?;
+        }
+
+        match *orders == expected {
+            true => Some(self.rate),
+            false => None,
+        }
+    }
+}
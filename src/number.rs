@@ -0,0 +1,256 @@
+//! Exact rational arithmetic used to evaluate rates and counts written as decimals or expressions.
+//! Values are kept as reduced fractions so no precision is lost to floating point rounding
+//! before they are converted into the integer types used by the engine.
+
+use std::fmt;
+
+/// reasons an arithmetic operation could not be carried out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArithmeticError {
+    DivisionByZero,
+    /// an intermediate value does not fit in an i128 fraction
+    Overflow,
+    /// an exponent is not a whole number or is too large to raise a value to
+    BadExponent,
+    /// a literal is too close to zero for an i128 fraction, so it has a fractional part which can not be kept exactly
+    Underflow,
+}
+
+/// reasons a value could not be converted into an integer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IntegerConversionError {
+    /// the value has a fractional part, ie 0.5 or 1e-3
+    NotIntegral,
+    Negative,
+    /// the value is larger than u64::MAX
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rational {
+    numerator: i128,
+    // always positive and coprime with the numerator
+    denominator: i128,
+}
+
+impl Rational {
+    pub(crate) fn new(numerator: i128, denominator: i128) -> Result<Self, ArithmeticError> {
+        if denominator == 0 {
+            return Err(ArithmeticError::DivisionByZero);
+        }
+        let divisor = gcd(numerator, denominator);
+        let (mut numerator, mut denominator) = (numerator / divisor, denominator / divisor);
+        if denominator < 0 {
+            numerator = numerator.checked_neg().ok_or(ArithmeticError::Overflow)?;
+            denominator = denominator.checked_neg().ok_or(ArithmeticError::Overflow)?;
+        }
+        Ok(Rational { numerator, denominator })
+    }
+
+    pub(crate) fn from_integer(value: u64) -> Self {
+        Rational { numerator: i128::from(value), denominator: 1 }
+    }
+
+    /// Converts a literal such as `10`, `2.0`, `0.5`, `1e3` or `2.5E-4` into the exact value it represents
+    pub(crate) fn from_decimal(literal: &str) -> Result<Self, ArithmeticError> {
+        let (mantissa, exponent) = match literal.find(['e', 'E']) {
+            Some(index) => (&literal[..index], &literal[index + 1..]),
+            None => (literal, ""),
+        };
+        let (integer_part, fraction_part) = match mantissa.find('.') {
+            Some(index) => (&mantissa[..index], &mantissa[index + 1..]),
+            None => (mantissa, ""),
+        };
+
+        // the significant digits of the mantissa without leading zeros
+        let digits = format!("{}{}", integer_part, fraction_part);
+        let digits = digits.trim_start_matches('0');
+        if digits.is_empty() {
+            return Ok(Rational::from_integer(0));
+        }
+
+        // an exponent too long to fit an i64 is far outside of the range of an i128 either way, so it saturates
+        let exponent: i64 = match exponent {
+            "" => 0,
+            exponent if exponent.starts_with('-') => exponent.parse().unwrap_or(i64::MIN),
+            exponent => exponent.trim_start_matches('+').parse().unwrap_or(i64::MAX),
+        };
+
+        // value is significand * 10^scale, trailing zeros are moved into the scale so long literals like 1.000 still fit
+        let significant_digits = digits.trim_end_matches('0');
+        let trailing_zeros = (digits.len() - significant_digits.len()) as i64;
+        let significand: i128 = significant_digits.parse().map_err(|_| ArithmeticError::Overflow)?;
+        let scale = exponent
+            .saturating_sub(fraction_part.len() as i64)
+            .saturating_add(trailing_zeros);
+        let power = u32::try_from(scale.unsigned_abs()).ok().and_then(|magnitude| 10i128.checked_pow(magnitude));
+
+        match (scale < 0, power) {
+            (true, Some(power)) => Rational::new(significand, power),
+            // the significand is below 10^39 so a literal this small lies strictly between zero and one
+            (true, None) => Result::Err(ArithmeticError::Underflow),
+            (false, Some(power)) => Rational::new(significand.checked_mul(power).ok_or(ArithmeticError::Overflow)?, 1),
+            (false, None) => Result::Err(ArithmeticError::Overflow),
+        }
+    }
+
+    pub(crate) fn add(self, other: Self) -> Result<Self, ArithmeticError> {
+        let numerator = self.numerator.checked_mul(other.denominator)
+            .zip(other.numerator.checked_mul(self.denominator))
+            .and_then(|(left, right)| left.checked_add(right));
+        let denominator = self.denominator.checked_mul(other.denominator);
+        match (numerator, denominator) {
+            (Some(numerator), Some(denominator)) => Rational::new(numerator, denominator),
+            _ => Err(ArithmeticError::Overflow),
+        }
+    }
+
+    pub(crate) fn subtract(self, other: Self) -> Result<Self, ArithmeticError> {
+        self.add(other.negate()?)
+    }
+
+    pub(crate) fn multiply(self, other: Self) -> Result<Self, ArithmeticError> {
+        match (self.numerator.checked_mul(other.numerator), self.denominator.checked_mul(other.denominator)) {
+            (Some(numerator), Some(denominator)) => Rational::new(numerator, denominator),
+            _ => Err(ArithmeticError::Overflow),
+        }
+    }
+
+    pub(crate) fn divide(self, other: Self) -> Result<Self, ArithmeticError> {
+        if other.numerator == 0 {
+            return Err(ArithmeticError::DivisionByZero);
+        }
+        self.multiply(Rational::new(other.denominator, other.numerator)?)
+    }
+
+    pub(crate) fn negate(self) -> Result<Self, ArithmeticError> {
+        match self.numerator.checked_neg() {
+            Some(numerator) => Ok(Rational { numerator, denominator: self.denominator }),
+            None => Err(ArithmeticError::Overflow),
+        }
+    }
+
+    /// raise to a whole number power, negative powers take the reciprocal
+    pub(crate) fn power(self, exponent: Self) -> Result<Self, ArithmeticError> {
+        if exponent.denominator != 1 {
+            return Err(ArithmeticError::BadExponent);
+        }
+        let magnitude = u32::try_from(exponent.numerator.unsigned_abs()).map_err(|_| ArithmeticError::BadExponent)?;
+        let raised = match (self.numerator.checked_pow(magnitude), self.denominator.checked_pow(magnitude)) {
+            (Some(numerator), Some(denominator)) => Rational::new(numerator, denominator)?,
+            _ => return Err(ArithmeticError::Overflow),
+        };
+        if exponent.numerator < 0 {
+            Rational::from_integer(1).divide(raised)
+        } else {
+            Ok(raised)
+        }
+    }
+
+    pub(crate) fn to_u64(self) -> Result<u64, IntegerConversionError> {
+        if self.denominator != 1 {
+            Err(IntegerConversionError::NotIntegral)
+        } else if self.numerator < 0 {
+            Err(IntegerConversionError::Negative)
+        } else {
+            u64::try_from(self.numerator).map_err(|_| IntegerConversionError::Overflow)
+        }
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.denominator {
+            1 => write!(f, "{}", self.numerator),
+            denominator => write!(f, "{}/{}", self.numerator, denominator),
+        }
+    }
+}
+
+/// Whether text is a literal [Rational::from_decimal] accepts, for numbers which did not come through the csv grammar
+pub(crate) fn is_decimal(literal: &str) -> bool {
+    let (mantissa, exponent) = match literal.find(['e', 'E']) {
+        Some(index) => (&literal[..index], Some(&literal[index + 1..])),
+        None => (literal, None),
+    };
+    let (integer_part, fraction_part) = match mantissa.find('.') {
+        Some(index) => (&mantissa[..index], &mantissa[index + 1..]),
+        None => (mantissa, ""),
+    };
+    let is_digits = |text: &str| text.bytes().all(|byte| byte.is_ascii_digit());
+    let valid_exponent = match exponent.map(|exponent| exponent.strip_prefix(['+', '-']).unwrap_or(exponent)) {
+        Some(digits) => !digits.is_empty() && is_digits(digits),
+        None => true,
+    };
+
+    !(integer_part.is_empty() && fraction_part.is_empty()) && is_digits(integer_part) && is_digits(fraction_part) && valid_exponent
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    // a only exceeds i128::MAX when both inputs are i128::MIN, and is zero when both are zero
+    i128::try_from(a).unwrap_or(1).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArithmeticError, IntegerConversionError, Rational, is_decimal};
+
+    fn decimal(literal: &str) -> Rational {
+        Rational::from_decimal(literal).unwrap()
+    }
+
+    #[test]
+    fn converts_exact_literals() {
+        assert_eq!(decimal("10000").to_u64(), Ok(10000));
+        assert_eq!(decimal("2.0").to_u64(), Ok(2));
+        assert_eq!(decimal("1e4").to_u64(), Ok(10000));
+        assert_eq!(decimal("2.5E3").to_u64(), Ok(2500));
+        assert_eq!(decimal("1500e-2").to_u64(), Ok(15));
+        assert_eq!(decimal(".5e1").to_u64(), Ok(5));
+        assert_eq!(decimal("0.0").to_u64(), Ok(0));
+        assert_eq!(decimal("18446744073709551615").to_u64(), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn rejects_inexact_literals() {
+        assert_eq!(decimal("0.5").to_u64(), Err(IntegerConversionError::NotIntegral));
+        assert_eq!(decimal("1e-3").to_u64(), Err(IntegerConversionError::NotIntegral));
+        assert_eq!(decimal("18446744073709551616").to_u64(), Err(IntegerConversionError::Overflow));
+        assert_eq!(decimal("1e20").to_u64(), Err(IntegerConversionError::Overflow));
+        assert_eq!(Rational::from_decimal("1e99999999999999999999"), Err(ArithmeticError::Overflow));
+    }
+
+    #[test]
+    fn rejects_literals_too_small_to_represent() {
+        assert_eq!(decimal("1e-38").to_u64(), Err(IntegerConversionError::NotIntegral));
+        assert_eq!(decimal("1e-38").multiply(decimal("1e38")).unwrap().to_u64(), Ok(1));
+        assert_eq!(Rational::from_decimal("1e-39"), Err(ArithmeticError::Underflow));
+        assert_eq!(Rational::from_decimal("1e-100"), Err(ArithmeticError::Underflow));
+        assert_eq!(Rational::from_decimal("123.456e-99999999999999999999"), Err(ArithmeticError::Underflow));
+    }
+
+    #[test]
+    fn recognises_decimal_literals() {
+        assert!(is_decimal("10") && is_decimal("2.5E-3") && is_decimal(".5") && is_decimal("1."));
+        assert!(!is_decimal("") && !is_decimal(".") && !is_decimal("-1") && !is_decimal("1e") && !is_decimal("INF"));
+        assert_eq!(decimal("0.5").to_string(), "1/2");
+        assert_eq!(decimal("1e3").to_string(), "1000");
+    }
+
+    #[test]
+    fn evaluates_exactly() {
+        let half = decimal("0.5");
+        assert_eq!(half.multiply(decimal("4")).unwrap().to_u64(), Ok(2));
+        assert_eq!(decimal("7").add(decimal("1")).unwrap().divide(decimal("2")).unwrap().to_u64(), Ok(4));
+        assert_eq!(decimal("2").power(decimal("8")).unwrap().to_u64(), Ok(256));
+        assert_eq!(decimal("2").power(decimal("-1")), Ok(half));
+        assert_eq!(decimal("1").subtract(decimal("3")).unwrap().to_u64(), Err(IntegerConversionError::Negative));
+        assert_eq!(decimal("1").divide(decimal("0")), Err(ArithmeticError::DivisionByZero));
+        assert_eq!(decimal("2").power(half), Err(ArithmeticError::BadExponent));
+        assert_eq!(decimal("10").power(decimal("100")), Err(ArithmeticError::Overflow));
+    }
+}
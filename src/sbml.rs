@@ -0,0 +1,732 @@
+//! Reads SBML level 3 models into a reaction network and writes reaction networks out as SBML level 3.
+//!
+//! Species become names with their `initialAmount` as the initial count, and every reaction becomes a [Reaction]
+//! whose rate is read from a mass action kinetic law such as `k * A * B`.
+//! Reversible reactions with a law of the form `kf * A * B - kr * C` become a forward and a reverse reaction.
+//! Species are named by their `name` when it is a valid csv name which no other species has as its name or id,
+//! and by their SBML id otherwise. A compartment in a kinetic law multiplies the rate by its size.
+//! Boundary and constant species are never changed by reactions, which the engine can not express, so are reported.
+//! MathML numbers may be `real`, `integer`, `e-notation` or `rational`, any other number type is reported.
+//! Anything the engine has no equivalent for, such as events, rules or other kinetic laws, is reported as a diagnostic.
+//!
+//! Exported models place every species in a single compartment and give each reaction a mass action kinetic law
+//! with its rate as a local parameter `k`. Species names which are not valid SBML ids, such as `setup.call`,
+//! are given an id with the invalid characters replaced by underscores and keep their original name in the `name` attribute.
+
+use std::{collections::{HashMap, HashSet}, io::{self, Write}, iter};
+
+use roxmltree::{Document, Node};
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, solution::{Name, Count, Solution}, reaction::{Reaction, term::Term}};
+
+use crate::{CSVparser, MarleaParserError, SourceSpan, mass_action::MassAction, number::{ArithmeticError, IntegerConversionError, Rational, is_decimal}, writer::CSVwriter};
+
+const SBML_NAMESPACE: &str = "http://www.sbml.org/sbml/level3/version2/core";
+const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+
+/// model level lists which have no equivalent in a reaction network
+const UNSUPPORTED_LISTS: [(&str, &str); 5] = [
+    ("listOfFunctionDefinitions", "function definition"),
+    ("listOfInitialAssignments", "initial assignment"),
+    ("listOfRules", "rule"),
+    ("listOfConstraints", "constraint"),
+    ("listOfEvents", "event"),
+];
+
+/// Interprets SBML documents as reaction networks
+pub(crate) struct SBMLparser;
+
+/// identifiers a kinetic law may refer to
+struct Symbols<'a> {
+    species: HashSet<&'a str>,
+    compartments: HashMap<&'a str, Node<'a, 'a>>,
+    parameters: HashMap<&'a str, Node<'a, 'a>>,
+}
+
+/// identifiers visible to a single kinetic law
+struct Scope<'s, 'a> {
+    symbols: &'s Symbols<'a>,
+    local_parameters: HashMap<&'a str, Node<'a, 'a>>,
+}
+
+impl SBMLparser {
+    /// parse an SBML document into a reaction network, failing on the first unsupported construct
+    pub(crate) fn as_reaction_network(source: &str) -> Result<ReactionNetwork, MarleaParserError> {
+        let (reaction_network, mut diagnostics) = Self::as_reaction_network_recovering(source);
+        match diagnostics.is_empty() {
+            true => Result::Ok(reaction_network),
+            false => Result::Err(diagnostics.remove(0)),
+        }
+    }
+
+    /// parse an SBML document into a reaction network, skipping anything which can not be imported.
+    /// Returns the network built from every supported species and reaction along with a diagnostic for everything else.
+    pub(crate) fn as_reaction_network_recovering(source: &str) -> (ReactionNetwork, Vec<MarleaParserError>) {
+        let mut reactions = HashSet::new();
+        let mut species_counts = HashMap::new();
+        let mut diagnostics = Vec::new();
+
+        match Document::parse(source) {
+            Ok(document) => Self::interpret_document(source, &document, &mut reactions, &mut species_counts, &mut diagnostics),
+            Err(err) => {
+                let location = SourceSpan::from_line_column(source, err.pos().row as usize, err.pos().col as usize);
+                diagnostics.push(MarleaParserError::Xml { location, source: Box::new(err) });
+            }
+        }
+
+        // any species used by a reaction but never declared still needs a count
+        for reaction in &reactions {
+            for term in reaction.get_reactants().iter().chain(reaction.get_products()) {
+                species_counts.entry(term.get_species_name().clone()).or_insert(Count(0));
+            }
+        }
+
+        (ReactionNetwork::new(reactions, Solution { species_counts }), diagnostics)
+    }
+
+    fn interpret_document<'a>(
+        source: &str,
+        document: &'a Document<'a>,
+        reactions: &mut HashSet<Reaction>,
+        species_counts: &mut HashMap<Name, Count>,
+        diagnostics: &mut Vec<MarleaParserError>,
+    ) {
+        let root = document.root_element();
+        if root.tag_name().name() != "sbml" || root.attribute("level") != Some("3") {
+            diagnostics.push(MarleaParserError::InvalidSbml { location: Self::span(source, root), reason: String::from("expected an sbml level 3 document") });
+            return;
+        }
+        let model = match Self::child(root, "model") {
+            Some(model) => model,
+            None => {
+                diagnostics.push(MarleaParserError::InvalidSbml { location: Self::span(source, root), reason: String::from("document has no model") });
+                return;
+            }
+        };
+
+        for (list, construct) in UNSUPPORTED_LISTS {
+            for node in Self::list(model, list) {
+                diagnostics.push(MarleaParserError::UnsupportedSbml { location: Self::span(source, node), construct });
+            }
+        }
+
+        let mut symbols = Symbols { species: HashSet::new(), compartments: HashMap::new(), parameters: HashMap::new() };
+        for compartment in Self::list(model, "listOfCompartments") {
+            if let Some(id) = compartment.attribute("id") {
+                symbols.compartments.insert(id, compartment);
+            }
+        }
+        for parameter in Self::list(model, "listOfParameters") {
+            if let Some(id) = parameter.attribute("id") {
+                symbols.parameters.insert(id, parameter);
+            }
+        }
+
+        // kinetic laws refer to species by id, so species are only renamed once their reactions are built
+        let names = Self::species_names(model);
+        for species in Self::list(model, "listOfSpecies") {
+            // a species with an unsupported initial value may still be used by reactions
+            if let Some(id) = species.attribute("id") {
+                symbols.species.insert(id);
+            }
+            match Self::as_species_count(source, species) {
+                Ok((name, count)) => {
+                    species_counts.insert(Self::rename(&name, &names), count);
+                },
+                Err(msg) => diagnostics.push(msg),
+            }
+        }
+
+        for reaction in Self::list(model, "listOfReactions") {
+            match Self::as_reactions(source, reaction, &symbols) {
+                Ok(interpreted) => reactions.extend(interpreted.iter().map(|reaction| Self::renamed(reaction, &names))),
+                Err(msg) => diagnostics.push(msg),
+            }
+        }
+    }
+
+    /// the `name` of each species which is a valid csv name that no other species has as its name or id,
+    /// so names such as `setup.call` which were exported with the id `setup_call` are read back as they were written
+    fn species_names<'a>(model: Node<'a, 'a>) -> HashMap<&'a str, &'a str> {
+        let species = Self::list(model, "listOfSpecies").into_iter()
+            .filter_map(|species| species.attribute("id").map(|id| (id, species.attribute("name").filter(|name| *name != id))))
+            .collect::<Vec<(&str, Option<&str>)>>();
+
+        let mut uses: HashMap<&str, usize> = HashMap::new();
+        for (id, name) in &species {
+            for identifier in iter::once(*id).chain(*name) {
+                *uses.entry(identifier).or_insert(0) += 1;
+            }
+        }
+
+        species.into_iter()
+        .filter_map(|(id, name)| name.filter(|name| uses[name] == 1 && CSVparser::is_name(name)).map(|name| (id, name)))
+        .collect()
+    }
+
+    fn rename(name: &Name, names: &HashMap<&str, &str>) -> Name {
+        match names.get(name.0.as_str()) {
+            Some(renamed) => Name(renamed.to_string()),
+            None => name.clone(),
+        }
+    }
+
+    fn renamed(reaction: &Reaction, names: &HashMap<&str, &str>) -> Reaction {
+        let rename = |terms: &[Term]| terms.iter()
+            .map(|term| Term::new(Self::rename(term.get_species_name(), names), Count(term.get_coefficient().0)))
+            .collect::<Vec<Term>>();
+        Reaction::new(rename(reaction.get_reactants()), rename(reaction.get_products()), reaction.get_reaction_rate())
+    }
+
+    /// a species element as its name and initial amount
+    fn as_species_count(source: &str, species: Node) -> Result<(Name, Count), MarleaParserError> {
+        let location = Self::span(source, species);
+        let id = match species.attribute("id") {
+            Some(id) => id,
+            None => return Result::Err(MarleaParserError::InvalidSbml { location, reason: String::from("species has no id") }),
+        };
+        if species.has_attribute("initialConcentration") {
+            return Result::Err(MarleaParserError::UnsupportedSbml { location, construct: "initial concentration" });
+        }
+        if species.attribute("boundaryCondition") == Some("true") {
+            return Result::Err(MarleaParserError::UnsupportedSbml { location, construct: "boundary species" });
+        }
+        if species.attribute("constant") == Some("true") {
+            return Result::Err(MarleaParserError::UnsupportedSbml { location, construct: "constant species" });
+        }
+
+        match species.attribute("initialAmount") {
+            Some(amount) => match Self::as_number(amount).map(|amount| amount.map(Rational::to_u64)) {
+                Some(Ok(Ok(count))) => Result::Ok((Name(id.to_string()), Count(count))),
+                Some(Ok(Err(IntegerConversionError::NotIntegral)) | Err(ArithmeticError::Underflow)) => Result::Err(MarleaParserError::NonIntegral { location, text: amount.to_string() }),
+                _ => Result::Err(MarleaParserError::BadSpeciesCount { location, text: amount.to_string() }),
+            },
+            None => Result::Ok((Name(id.to_string()), Count(0))),
+        }
+    }
+
+    /// a reaction element as a single reaction, or a forward and reverse reaction for reversible mass action laws
+    fn as_reactions(source: &str, reaction: Node, symbols: &Symbols) -> Result<Vec<Reaction>, MarleaParserError> {
+        let location = Self::span(source, reaction);
+        let id = reaction.attribute("id").unwrap_or_default().to_string();
+        let reactants = Self::as_terms(source, reaction, "listOfReactants", symbols)?;
+        let products = Self::as_terms(source, reaction, "listOfProducts", symbols)?;
+
+        let kinetic_law = match Self::child(reaction, "kineticLaw") {
+            Some(kinetic_law) => kinetic_law,
+            None => return Result::Err(MarleaParserError::NotMassAction { location, reaction: id }),
+        };
+        let math = match Self::child(kinetic_law, "math").and_then(|math| math.children().find(Node::is_element)) {
+            Some(math) => math,
+            None => return Result::Err(MarleaParserError::NotMassAction { location: Self::span(source, kinetic_law), reaction: id }),
+        };
+
+        // local parameters shadow global ones
+        let mut local_parameters = HashMap::new();
+        for list in ["listOfLocalParameters", "listOfParameters"] {
+            for parameter in Self::list(kinetic_law, list) {
+                if let Some(parameter_id) = parameter.attribute("id") {
+                    local_parameters.insert(parameter_id, parameter);
+                }
+            }
+        }
+        let scope = Scope { symbols, local_parameters };
+
+        if let Some(rate) = Self::mass_action_rate(source, math, &reactants, &scope)? {
+            return Result::Ok(vec![Reaction::new(reactants, products, Self::as_rate(source, math, rate)?)]);
+        }
+
+        // a reversible law is the difference of a forward and a reverse mass action term
+        if reaction.attribute("reversible") == Some("true") && Self::is_apply(math, "minus") {
+            let operands = math.children().filter(Node::is_element).skip(1).collect::<Vec<Node>>();
+            if let [forward, reverse] = operands[..] {
+                let forward_rate = Self::mass_action_rate(source, forward, &reactants, &scope)?;
+                let reverse_rate = Self::mass_action_rate(source, reverse, &products, &scope)?;
+                if let (Some(forward_rate), Some(reverse_rate)) = (forward_rate, reverse_rate) {
+                    return Result::Ok(vec![
+                        Reaction::new(reactants.clone(), products.clone(), Self::as_rate(source, forward, forward_rate)?),
+                        Reaction::new(products, reactants, Self::as_rate(source, reverse, reverse_rate)?),
+                    ]);
+                }
+            }
+        }
+
+        Result::Err(MarleaParserError::NotMassAction { location: Self::span(source, math), reaction: id })
+    }
+
+    /// species references in one of a reaction's lists as terms
+    fn as_terms(source: &str, reaction: Node, list: &str, symbols: &Symbols) -> Result<Vec<Term>, MarleaParserError> {
+        let mut terms = Vec::new();
+        for reference in Self::list(reaction, list) {
+            let location = Self::span(source, reference);
+            let species = match reference.attribute("species") {
+                Some(species) if symbols.species.contains(species) => species,
+                Some(species) => return Result::Err(MarleaParserError::InvalidSbml { location, reason: format!("species `{}` is not declared", species) }),
+                None => return Result::Err(MarleaParserError::InvalidSbml { location, reason: String::from("species reference has no species") }),
+            };
+
+            let stoichiometry = reference.attribute("stoichiometry").unwrap_or("1");
+            match Self::as_number(stoichiometry).map(|stoichiometry| stoichiometry.map(Rational::to_u64)) {
+                Some(Ok(Err(IntegerConversionError::NotIntegral)) | Err(ArithmeticError::Underflow)) => {
+                    return Result::Err(MarleaParserError::NonIntegral { location, text: stoichiometry.to_string() })
+                },
+                Some(Ok(Ok(0)) | Ok(Err(_)) | Err(_)) | None => {
+                    return Result::Err(MarleaParserError::BadCoefficient { location, text: stoichiometry.to_string() })
+                },
+                Some(Ok(Ok(coefficient))) => terms.push(Term::new(Name(species.to_string()), Count(coefficient))),
+            }
+        }
+        Result::Ok(terms)
+    }
+
+    /// rate constant of a mass action term over exactly the given species,
+    /// or None if the term is not a product of constants and those species
+    fn mass_action_rate(source: &str, math: Node, species: &[Term], scope: &Scope) -> Result<Option<Rational>, MarleaParserError> {
+        let symbols = scope.symbols;
+        let mut factors = Vec::new();
+        if !Self::collect_factors(source, math, 1, &mut factors)? {
+            return Result::Ok(None);
+        }
+
+        let mut mass_action = MassAction::new();
+        for (factor, power) in factors {
+            let text = factor.text().unwrap_or_default().trim();
+            let value = match factor.tag_name().name() {
+                "ci" if symbols.species.contains(text) => {
+                    mass_action.species(text, power);
+                    continue;
+                },
+                // a compartment has no default size, so one without a size can not be evaluated
+                "ci" if symbols.compartments.contains_key(text) => match symbols.compartments[text].attribute("size") {
+                    Some(size) => Self::as_rate_constant(source, factor, size)?,
+                    None => return Result::Err(MarleaParserError::UnsupportedSbml { location: Self::span(source, factor), construct: "compartment without a size" }),
+                },
+                "ci" => match scope.local_parameters.get(text).or_else(|| symbols.parameters.get(text)) {
+                    Some(parameter) => match parameter.attribute("value") {
+                        Some(value) => Self::as_rate_constant(source, *parameter, value)?,
+                        None => None,
+                    },
+                    None => return Result::Err(MarleaParserError::InvalidSbml { location: Self::span(source, factor), reason: format!("`{}` is not declared", text) }),
+                },
+                _ => Self::as_cn(source, factor)?,
+            };
+
+            let value = match value {
+                Some(value) => value,
+                None => return Result::Ok(None),
+            };
+            if mass_action.constant(value, power).is_err() {
+                return Result::Err(MarleaParserError::ArithmeticOverflow { location: Self::span(source, factor) });
+            }
+        }
+
+        Result::Ok(mass_action.rate_over(species))
+    }
+
+    /// flatten products and whole number powers into their leaf identifiers and numbers along with the power each is raised to
+    fn collect_factors<'a>(source: &str, math: Node<'a, 'a>, power: u64, factors: &mut Vec<(Node<'a, 'a>, u64)>) -> Result<bool, MarleaParserError> {
+        match math.tag_name().name() {
+            "ci" | "cn" => {
+                factors.push((math, power));
+                Result::Ok(true)
+            },
+            "apply" if Self::is_apply(math, "times") => {
+                for operand in math.children().filter(Node::is_element).skip(1) {
+                    if !Self::collect_factors(source, operand, power, factors)? {
+                        return Result::Ok(false);
+                    }
+                }
+                Result::Ok(true)
+            },
+            "apply" if Self::is_apply(math, "power") => {
+                let operands = math.children().filter(Node::is_element).skip(1).collect::<Vec<Node>>();
+                match operands[..] {
+                    [base, exponent] if exponent.tag_name().name() == "cn" => {
+                        match Self::as_cn(source, exponent)?.and_then(|exponent| exponent.to_u64().ok()).and_then(|exponent| exponent.checked_mul(power)) {
+                            Some(power) => Self::collect_factors(source, base, power, factors),
+                            None => Result::Ok(false),
+                        }
+                    },
+                    _ => Result::Ok(false),
+                }
+            },
+            _ => Result::Ok(false),
+        }
+    }
+
+    /// the value of a MathML cn element, or None if its text is not a number.
+    /// `e-notation` and `rational` numbers are written as two integers separated by a `<sep/>` element, ie `1<sep/>3` for 1000
+    fn as_cn(source: &str, cn: Node) -> Result<Option<Rational>, MarleaParserError> {
+        let location = Self::span(source, cn);
+        let mut parts = vec![String::new()];
+        for child in cn.children() {
+            if let (true, Some(part)) = (child.is_text(), parts.last_mut()) {
+                part.push_str(child.text().unwrap_or_default());
+            } else if child.tag_name().name() == "sep" {
+                parts.push(String::new());
+            } else if child.is_element() {
+                return Result::Err(MarleaParserError::InvalidSbml { location, reason: format!("unexpected `{}` element in a number", child.tag_name().name()) });
+            }
+        }
+        let parts = parts.iter().map(|part| part.trim()).collect::<Vec<&str>>();
+
+        let kind = cn.attribute("type").unwrap_or("real");
+        match (kind, &parts[..]) {
+            ("real", [value]) => Self::as_rate_constant(source, cn, value),
+            ("integer", [value]) => Result::Ok(value.parse::<u64>().ok().map(Rational::from_integer)),
+            ("e-notation", [mantissa, exponent]) => match exponent.parse::<i64>() {
+                Ok(_) => Self::as_rate_constant(source, cn, &format!("{}e{}", mantissa, exponent)),
+                Err(_) => Result::Ok(None),
+            },
+            ("rational", [numerator, denominator]) => match (numerator.parse::<i128>(), denominator.parse::<i128>()) {
+                (Ok(_), Ok(0)) => Result::Err(MarleaParserError::DivisionByZero { location }),
+                (Ok(numerator), Ok(denominator)) => Rational::new(numerator, denominator).map(Some).map_err(|_| MarleaParserError::ArithmeticOverflow { location }),
+                _ => Result::Ok(None),
+            },
+            ("real" | "integer", _) => Result::Err(MarleaParserError::UnsupportedSbml { location, construct: "`sep` in a real or integer number" }),
+            ("e-notation" | "rational", _) => Result::Err(MarleaParserError::InvalidSbml { location, reason: format!("{} numbers are two integers separated by `<sep/>`", kind) }),
+            _ => Result::Err(MarleaParserError::UnsupportedSbml { location, construct: "number type" }),
+        }
+    }
+
+    fn as_rate(source: &str, math: Node, rate: Rational) -> Result<u64, MarleaParserError> {
+        let location = Self::span(source, math);
+        match rate.to_u64() {
+            Ok(0) => Result::Err(MarleaParserError::ZeroRate { location, text: rate.to_string() }),
+            Ok(rate) => Result::Ok(rate),
+            Err(IntegerConversionError::NotIntegral) => Result::Err(MarleaParserError::InexactRate { location, text: rate.to_string() }),
+            Err(_) => Result::Err(MarleaParserError::BadRate { location, text: rate.to_string() }),
+        }
+    }
+
+    /// a number written as an SBML attribute or MathML cn element, None if the text is not a decimal
+    fn as_number(text: &str) -> Option<Result<Rational, ArithmeticError>> {
+        let text = text.trim();
+        match is_decimal(text) {
+            true => Some(Rational::from_decimal(text)),
+            false => None,
+        }
+    }
+
+    /// a number in a rate law, or None if it can not be read exactly.
+    /// A number too small to represent makes the rate inexact
+    fn as_rate_constant(source: &str, node: Node, text: &str) -> Result<Option<Rational>, MarleaParserError> {
+        match Self::as_number(text) {
+            Some(Err(ArithmeticError::Underflow)) => Result::Err(MarleaParserError::InexactRate { location: Self::span(source, node), text: text.trim().to_string() }),
+            number => Result::Ok(number.and_then(Result::ok)),
+        }
+    }
+
+    fn is_apply(math: Node, operator: &str) -> bool {
+        math.tag_name().name() == "apply" && math.children().find(Node::is_element).map(|node| node.tag_name().name()) == Some(operator)
+    }
+
+    fn child<'a>(node: Node<'a, 'a>, name: &str) -> Option<Node<'a, 'a>> {
+        node.children().find(|child| child.tag_name().name() == name)
+    }
+
+    /// elements inside of a listOf element, empty if the list is missing
+    fn list<'a>(node: Node<'a, 'a>, name: &str) -> Vec<Node<'a, 'a>> {
+        match Self::child(node, name) {
+            Some(list) => list.children().filter(Node::is_element).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// location of an element within the document
+    fn span(source: &str, node: Node) -> SourceSpan {
+        let range = node.range();
+        let mut location = SourceSpan::from_offset(source, range.start);
+        location.end = range.end;
+        location
+    }
+}
+
+/// Serializes a [ReactionNetwork] into an SBML level 3 version 2 document
+pub struct SBMLwriter;
+
+impl SBMLwriter {
+    /// write the network as an SBML document, species and reactions are sorted so output is deterministic
+    pub fn as_sbml(reaction_network: &ReactionNetwork) -> String {
+        // species used by reactions are included even if the solution has no count for them
+        let mut names = reaction_network.get_solution().species_counts.keys()
+            .chain(reaction_network.get_reactions().iter().flat_map(|reaction| reaction.get_reactants().iter().chain(reaction.get_products())).map(Term::get_species_name))
+            .map(|name| name.0.as_str())
+            .collect::<Vec<&str>>();
+        names.sort_unstable();
+        names.dedup();
+        let ids = Self::species_ids(&names);
+
+        let mut reactions = reaction_network.get_reactions().iter().collect::<Vec<&Reaction>>();
+        reactions.sort_by_cached_key(|reaction| CSVwriter::as_row(reaction));
+
+        let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        output.push_str(&format!("<sbml xmlns=\"{}\" level=\"3\" version=\"2\">\n", SBML_NAMESPACE));
+        output.push_str("  <model id=\"marlea_network\">\n");
+        output.push_str("    <listOfCompartments>\n");
+        output.push_str("      <compartment id=\"cell\" spatialDimensions=\"3\" size=\"1\" constant=\"true\"/>\n");
+        output.push_str("    </listOfCompartments>\n");
+
+        output.push_str("    <listOfSpecies>\n");
+        for name in &names {
+            let count = reaction_network.get_solution().species_counts.get(&Name(name.to_string())).map_or(0, |count| count.0);
+            output.push_str(&format!(
+                "      <species id=\"{}\" name=\"{}\" compartment=\"cell\" initialAmount=\"{}\" hasOnlySubstanceUnits=\"true\" boundaryCondition=\"false\" constant=\"false\"/>\n",
+                ids[name], Self::escape(name), count,
+            ));
+        }
+        output.push_str("    </listOfSpecies>\n");
+
+        output.push_str("    <listOfReactions>\n");
+        for (index, reaction) in reactions.iter().enumerate() {
+            output.push_str(&format!("      <reaction id=\"r{}\" reversible=\"false\">\n", index + 1));
+            Self::write_terms(&mut output, "listOfReactants", reaction.get_reactants(), &ids);
+            Self::write_terms(&mut output, "listOfProducts", reaction.get_products(), &ids);
+            output.push_str("        <kineticLaw>\n");
+            output.push_str(&format!("          <math xmlns=\"{}\">\n", MATHML_NAMESPACE));
+            Self::write_mass_action(&mut output, reaction.get_reactants(), &ids);
+            output.push_str("          </math>\n");
+            output.push_str("          <listOfLocalParameters>\n");
+            output.push_str(&format!("            <localParameter id=\"k\" value=\"{}\"/>\n", reaction.get_reaction_rate()));
+            output.push_str("          </listOfLocalParameters>\n");
+            output.push_str("        </kineticLaw>\n");
+            output.push_str("      </reaction>\n");
+        }
+        output.push_str("    </listOfReactions>\n");
+
+        output.push_str("  </model>\n</sbml>\n");
+        output
+    }
+
+    /// write the network as an SBML document to any writer such as a file
+    pub fn write(reaction_network: &ReactionNetwork, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(Self::as_sbml(reaction_network).as_bytes())
+    }
+
+    /// a list of species references, omitted entirely when there are no terms
+    fn write_terms(output: &mut String, list: &str, terms: &[Term], ids: &HashMap<&str, String>) {
+        if terms.is_empty() {
+            return;
+        }
+
+        output.push_str(&format!("        <{}>\n", list));
+        for term in terms {
+            output.push_str(&format!(
+                "          <speciesReference species=\"{}\" stoichiometry=\"{}\" constant=\"true\"/>\n",
+                ids[term.get_species_name().0.as_str()], term.get_coefficient().0,
+            ));
+        }
+        output.push_str(&format!("        </{}>\n", list));
+    }
+
+    /// the mass action law `k * A * B^2` over the reactants
+    fn write_mass_action(output: &mut String, reactants: &[Term], ids: &HashMap<&str, String>) {
+        if reactants.is_empty() {
+            output.push_str("            <ci> k </ci>\n");
+            return;
+        }
+
+        output.push_str("            <apply>\n              <times/>\n              <ci> k </ci>\n");
+        for term in reactants {
+            let id = &ids[term.get_species_name().0.as_str()];
+            match term.get_coefficient().0 {
+                1 => output.push_str(&format!("              <ci> {} </ci>\n", id)),
+                coefficient => output.push_str(&format!("              <apply> <power/> <ci> {} </ci> <cn type=\"integer\"> {} </cn> </apply>\n", id, coefficient)),
+            }
+        }
+        output.push_str("            </apply>\n");
+    }
+
+    /// a unique valid SBML id for every species name
+    fn species_ids<'a>(names: &[&'a str]) -> HashMap<&'a str, String> {
+        let mut ids = HashMap::new();
+        let mut used = HashSet::new();
+        // `k` is the local rate parameter of every kinetic law so it may not be shadowed by a species
+        used.insert(String::from("k"));
+
+        for name in names {
+            let mut base = name.chars()
+                .map(|character| if character.is_ascii_alphanumeric() || character == '_' { character } else { '_' })
+                .collect::<String>();
+            if !base.starts_with(|character: char| character.is_ascii_alphabetic() || character == '_') {
+                base.insert(0, '_');
+            }
+
+            let mut id = base.clone();
+            let mut suffix = 1;
+            while used.contains(&id) {
+                suffix += 1;
+                id = format!("{}_{}", base, suffix);
+            }
+            used.insert(id.clone());
+            ids.insert(*name, id);
+        }
+
+        ids
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::Path};
+
+    use roxmltree::Document;
+
+    use crate::{CSVparser, MarleaParser, MarleaParserError, sbml::{SBMLparser, SBMLwriter}};
+
+    fn model(body: &str) -> String {
+        format!(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<sbml xmlns=\"http://www.sbml.org/sbml/level3/version2/core\" level=\"3\" version=\"2\">\n",
+            "<model id=\"test\">\n{}</model>\n</sbml>\n",
+        ), body)
+    }
+
+    #[test]
+    fn sbml_parser_imports_mass_action_reactions() {
+        let imported = MarleaParser::parse(&Path::new("test_data").join("sbml").join("mass_action.xml")).unwrap();
+        let literal = CSVparser::as_reaction_network("A + 2 B => C,10,\nC <=> D,4,2,\nNULL => A,5,\nA,10,\nB,20,\n").unwrap();
+        assert_eq!(imported.get_reactions(), literal.get_reactions());
+        assert_eq!(imported.get_solution().species_counts, literal.get_solution().species_counts);
+    }
+
+    #[test]
+    fn sbml_parser_reports_unsupported_constructs() {
+        let source = model(concat!(
+            "<listOfSpecies><species id=\"A\" initialAmount=\"1\"/><species id=\"B\" initialConcentration=\"0.5\"/></listOfSpecies>\n",
+            "<listOfReactions>\n",
+            "<reaction id=\"r1\" reversible=\"false\">\n",
+            "<listOfReactants><speciesReference species=\"A\" stoichiometry=\"1\"/></listOfReactants>\n",
+            "<kineticLaw><math xmlns=\"http://www.w3.org/1998/Math/MathML\">\n",
+            "<apply><divide/><ci>A</ci><cn>2</cn></apply>\n",
+            "</math></kineticLaw>\n",
+            "</reaction>\n",
+            "</listOfReactions>\n",
+            "<listOfRules><assignmentRule variable=\"A\"/></listOfRules>\n",
+            "<listOfEvents><event id=\"e1\"/></listOfEvents>\n",
+        ));
+
+        let (_, diagnostics) = SBMLparser::as_reaction_network_recovering(&source);
+        assert_eq!(diagnostics.len(), 4);
+        assert!(matches!(&diagnostics[0], MarleaParserError::UnsupportedSbml { construct: "rule", location } if location.line == 13));
+        assert!(matches!(&diagnostics[1], MarleaParserError::UnsupportedSbml { construct: "event", .. }));
+        assert!(matches!(&diagnostics[2], MarleaParserError::UnsupportedSbml { construct: "initial concentration", .. }));
+        assert!(matches!(&diagnostics[3], MarleaParserError::NotMassAction { reaction, location } if reaction == "r1" && location.line == 9));
+
+        assert!(matches!(SBMLparser::as_reaction_network(&source), Err(MarleaParserError::UnsupportedSbml { construct: "rule", .. })));
+        assert!(matches!(SBMLparser::as_reaction_network("<sbml level=\"3\"><model>"), Err(MarleaParserError::Xml { .. })));
+    }
+
+    #[test]
+    fn sbml_parser_reads_typed_numbers() {
+        let reaction = |rate: &str| model(&format!(concat!(
+            "<listOfSpecies><species id=\"A\" initialAmount=\"1\"/></listOfSpecies>\n",
+            "<listOfReactions><reaction id=\"r1\" reversible=\"false\">\n",
+            "<listOfReactants><speciesReference species=\"A\" stoichiometry=\"2\"/></listOfReactants>\n",
+            "<kineticLaw><math xmlns=\"http://www.w3.org/1998/Math/MathML\">\n",
+            "<apply><times/>{}<apply><power/><ci>A</ci><cn type=\"integer\">2</cn></apply></apply>\n",
+            "</math></kineticLaw>\n",
+            "</reaction></listOfReactions>\n",
+        ), rate));
+        let rate = |rate: &str| SBMLparser::as_reaction_network(&reaction(rate)).map(|network| network.get_reactions().iter().next().unwrap().get_reaction_rate());
+
+        assert_eq!(rate("<cn type=\"e-notation\"> 1 <sep/> 3 </cn>").unwrap(), 1000);
+        assert_eq!(rate("<cn type=\"e-notation\">2.5<sep/>-1</cn><cn>4</cn>").unwrap(), 1);
+        assert_eq!(rate("<cn type=\"rational\">6<sep/>2</cn>").unwrap(), 3);
+        assert_eq!(rate("<cn type=\"integer\">7</cn>").unwrap(), 7);
+        assert!(matches!(rate("<cn type=\"rational\">1<sep/>2</cn>"), Err(MarleaParserError::InexactRate { .. })));
+        assert!(matches!(rate("<cn type=\"e-notation\">1<sep/>-40</cn>"), Err(MarleaParserError::InexactRate { .. })));
+        assert!(matches!(rate("<cn>1<sep/>2</cn>"), Err(MarleaParserError::UnsupportedSbml { .. })));
+        assert!(matches!(rate("<cn type=\"e-notation\">1</cn>"), Err(MarleaParserError::InvalidSbml { .. })));
+        assert!(matches!(rate("<cn type=\"complex-cartesian\">1<sep/>2</cn>"), Err(MarleaParserError::UnsupportedSbml { construct: "number type", .. })));
+    }
+
+    #[test]
+    fn sbml_parser_rejects_fixed_species_and_scales_by_compartments() {
+        let source = |species: &str, compartment: &str| model(&format!(concat!(
+            "<listOfCompartments>{}</listOfCompartments>\n",
+            "<listOfSpecies>{}</listOfSpecies>\n",
+            "<listOfReactions><reaction id=\"r1\" reversible=\"false\">\n",
+            "<listOfReactants><speciesReference species=\"A\" stoichiometry=\"1\"/></listOfReactants>\n",
+            "<kineticLaw><math xmlns=\"http://www.w3.org/1998/Math/MathML\">\n",
+            "<apply><times/><ci>cell</ci><cn>3</cn><ci>A</ci></apply>\n",
+            "</math></kineticLaw>\n",
+            "</reaction></listOfReactions>\n",
+        ), compartment, species));
+        let species = "<species id=\"A\" compartment=\"cell\" initialAmount=\"1\" boundaryCondition=\"false\" constant=\"false\"/>";
+        let rate = |source: &str| SBMLparser::as_reaction_network(source).map(|network| network.get_reactions().iter().next().unwrap().get_reaction_rate());
+
+        assert_eq!(rate(&source(species, "<compartment id=\"cell\" size=\"1\"/>")).unwrap(), 3);
+        assert_eq!(rate(&source(species, "<compartment id=\"cell\" size=\"2\"/>")).unwrap(), 6);
+        assert!(matches!(rate(&source(species, "<compartment id=\"cell\"/>")), Err(MarleaParserError::UnsupportedSbml { construct: "compartment without a size", .. })));
+
+        let boundary = species.replace("boundaryCondition=\"false\"", "boundaryCondition=\"true\"");
+        let constant = species.replace("constant=\"false\"", "constant=\"true\"");
+        assert!(matches!(rate(&source(&boundary, "<compartment id=\"cell\" size=\"1\"/>")), Err(MarleaParserError::UnsupportedSbml { construct: "boundary species", .. })));
+        assert!(matches!(rate(&source(&constant, "<compartment id=\"cell\" size=\"1\"/>")), Err(MarleaParserError::UnsupportedSbml { construct: "constant species", .. })));
+    }
+
+    #[test]
+    fn sbml_writer_exports_fibonacci() {
+        let network = MarleaParser::parse(&Path::new("test_data").join("Fibonacci_calculator.csv")).unwrap();
+        let exported = SBMLwriter::as_sbml(&network);
+
+        let document = Document::parse(&exported).unwrap();
+        let root = document.root_element();
+        assert_eq!(root.tag_name().namespace(), Some("http://www.sbml.org/sbml/level3/version2/core"));
+        assert_eq!((root.attribute("level"), root.attribute("version")), (Some("3"), Some("2")));
+
+        let species = document.descendants().filter(|node| node.has_tag_name("species")).collect::<Vec<_>>();
+        let reactions = document.descendants().filter(|node| node.has_tag_name("reaction")).collect::<Vec<_>>();
+        assert_eq!(species.len(), network.get_solution().species_counts.len());
+        assert_eq!(reactions.len(), network.get_reactions().len());
+
+        // ids are unique and valid, names keep the original species names
+        let ids = species.iter().map(|node| node.attribute("id").unwrap()).collect::<HashSet<&str>>();
+        assert_eq!(ids.len(), species.len());
+        assert!(ids.iter().all(|id| id.chars().all(|character| character.is_ascii_alphanumeric() || character == '_')));
+        assert!(species.iter().any(|node| node.attribute("id") == Some("setup_call") && node.attribute("name") == Some("setup.call")));
+        assert!(species.iter().any(|node| node.attribute("name") == Some("index") && node.attribute("initialAmount") == Some("10")));
+
+        // every reaction has a mass action law over declared species
+        for reaction in &reactions {
+            assert!(reaction.descendants().filter(|node| node.has_tag_name("speciesReference")).all(|node| ids.contains(node.attribute("species").unwrap())));
+            assert!(reaction.descendants().any(|node| node.has_tag_name("math") && node.tag_name().namespace() == Some("http://www.w3.org/1998/Math/MathML")));
+            let rate = reaction.descendants().find(|node| node.has_tag_name("localParameter")).and_then(|node| node.attribute("value"));
+            assert!(matches!(rate, Some("1") | Some("10000")));
+        }
+
+        // the exported laws are read back as the same rates
+        let (imported, diagnostics) = SBMLparser::as_reaction_network_recovering(&exported);
+        assert!(diagnostics.is_empty());
+        let mut imported_rates = imported.get_reactions().iter().map(|reaction| reaction.get_reaction_rate()).collect::<Vec<u64>>();
+        let mut rates = network.get_reactions().iter().map(|reaction| reaction.get_reaction_rate()).collect::<Vec<u64>>();
+        imported_rates.sort_unstable();
+        rates.sort_unstable();
+        assert_eq!(imported_rates, rates);
+    }
+
+    #[test]
+    fn sbml_writer_round_trips_names() {
+        let valid_ids = CSVparser::as_reaction_network("A + 2 B => C,10,\nC => NULL,3,\nNULL => A,5,\nA,10,\n").unwrap();
+        // the calculator's names such as `setup.call` are not valid SBML ids
+        let fibonacci = MarleaParser::parse(&Path::new("test_data").join("Fibonacci_calculator.csv")).unwrap();
+
+        for network in [valid_ids, fibonacci] {
+            let imported = SBMLparser::as_reaction_network(&SBMLwriter::as_sbml(&network)).unwrap();
+            assert_eq!(imported.get_reactions(), network.get_reactions());
+            assert_eq!(imported.get_solution().species_counts, network.get_solution().species_counts);
+        }
+
+        // a name is only used when it is a csv name no other species is known by
+        let species = "<listOfSpecies><species id=\"a\" name=\"b\"/><species id=\"b\" name=\"c d\"/><species id=\"e\" name=\"f.g\"/></listOfSpecies>\n";
+        let names = SBMLparser::as_reaction_network(&model(species)).unwrap().get_solution().species_counts.keys()
+            .map(|name| name.0.clone())
+            .collect::<HashSet<String>>();
+        assert_eq!(names, HashSet::from([String::from("a"), String::from("b"), String::from("f.g")]));
+    }
+}
@@ -0,0 +1,159 @@
+//! Groups the rows of a csv network into sections named by comment headers.
+//!
+//! A comment alone on its row, such as `,//Setup RXN Network,`, starts a section which runs until the next header.
+//! Comments written after a statement on the same row do not start a section, nor do blank `,,` rows end one.
+//! Reactions and species counts produced by `#use` and `#include` rows belong to the section of that row,
+//! and rows above the first header belong to a section without a header.
+//!
+//! ```text
+//! ,//Initialize solution,
+//! index,10,                               section "Initialize solution"
+//! ,//Setup RXN Network,
+//! fibonacci.call => setup.call,1,         section "Setup RXN Network"
+//! ```
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, reaction::Reaction, solution::{Name, Count}};
+
+use crate::{NetworkBuilder, syntax::Comment};
+
+/// The reactions and species counts written below a comment header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// the comment which started the section, None for rows above the first header
+    pub header: Option<Comment>,
+    pub reactions: Vec<Reaction>,
+    pub species_counts: Vec<(Name, Count)>,
+}
+
+impl Section {
+    pub(crate) fn new(header: Option<Comment>) -> Self {
+        Section { header, reactions: Vec::new(), species_counts: Vec::new() }
+    }
+
+    /// the words of the header comment, ie `Setup RXN Network`
+    pub fn name(&self) -> Option<&str> {
+        self.header.as_ref().map(Comment::content)
+    }
+}
+
+/// A reaction network along with the section each of its rows came from, see [crate::MarleaParser::parse_sections]
+pub struct SectionedNetwork {
+    network: ReactionNetwork,
+    sections: Vec<Section>,
+}
+
+impl SectionedNetwork {
+    pub(crate) fn new(network: ReactionNetwork, sections: Vec<Section>) -> Self {
+        SectionedNetwork { network, sections }
+    }
+
+    /// the whole network as [crate::MarleaParser::parse] would return it
+    pub fn network(&self) -> &ReactionNetwork {
+        &self.network
+    }
+
+    pub fn into_network(self) -> ReactionNetwork {
+        self.network
+    }
+
+    /// every section in source order
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub(crate) fn sections_mut(&mut self) -> &mut [Section] {
+        &mut self.sections
+    }
+
+    /// the reactions of every section with the given name, a name used by more than one header covers all of them
+    pub fn reactions_in_section(&self, name: &str) -> Vec<&Reaction> {
+        self.sections.iter()
+        .filter(|section| section.name() == Some(name))
+        .flat_map(|section| &section.reactions)
+        .collect()
+    }
+
+    /// the species counts set by rows of every section with the given name
+    pub fn species_counts_in_section(&self, name: &str) -> Vec<&(Name, Count)> {
+        self.sections.iter()
+        .filter(|section| section.name() == Some(name))
+        .flat_map(|section| &section.species_counts)
+        .collect()
+    }
+
+    /// name of the first section a reaction was written in, None if it is above the first header or not in the network
+    pub fn section_of(&self, reaction: &Reaction) -> Option<&str> {
+        self.sections.iter()
+        .find(|section| section.reactions.contains(reaction))
+        .and_then(Section::name)
+    }
+
+    /// rebuild the network leaving out every section with one of the given names,
+    /// species which are still used but were only given a count in a removed section start at zero
+    pub fn without_sections(&self, names: &[&str]) -> ReactionNetwork {
+        let mut builder = NetworkBuilder::default();
+        for section in &self.sections {
+            if section.name().is_some_and(|name| names.contains(&name)) {
+                continue;
+            }
+            for reaction in &section.reactions {
+                builder.add_reaction(reaction.clone());
+            }
+            for species_count in &section.species_counts {
+                builder.add_species_count(species_count.clone());
+            }
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::Path};
+
+    use marlea_engine::trial::reaction_network::{reaction::Reaction, solution::{Name, Count}};
+
+    use crate::MarleaParser;
+
+    #[test]
+    fn sections_follow_comment_headers() {
+        let source = concat!(
+            "A,1,\n",
+            ",//Initialize solution,\n",
+            "index,10,\n",
+            ",,\n",
+            "B,2,// not a header,\n",
+            ",//Setup RXN Network,\n",
+            "#param fast = 100\n",
+            "A => B,fast,\n",
+            "B <=> C,1,2,\n",
+        );
+        let sectioned = MarleaParser::parse_str_sections(source).unwrap();
+
+        let names = sectioned.sections().iter().map(|section| section.name()).collect::<Vec<Option<&str>>>();
+        assert_eq!(names, [None, Some("Initialize solution"), Some("Setup RXN Network")]);
+        assert_eq!(sectioned.sections()[2].header.as_ref().unwrap().span.line, 6);
+
+        assert_eq!(sectioned.species_counts_in_section("Initialize solution"), [&(Name(String::from("index")), Count(10)), &(Name(String::from("B")), Count(2))]);
+        assert_eq!(sectioned.reactions_in_section("Setup RXN Network").len(), 3);
+        assert!(sectioned.reactions_in_section("Missing").is_empty());
+
+        let reaction = sectioned.reactions_in_section("Setup RXN Network")[0];
+        assert_eq!(sectioned.section_of(reaction), Some("Setup RXN Network"));
+    }
+
+    #[test]
+    fn sections_can_be_disabled_by_name() {
+        let parsed = MarleaParser::parse(&Path::new("test_data").join("Fibonacci_calculator.csv")).unwrap();
+        let sectioned = MarleaParser::parse_sections(&Path::new("test_data").join("Fibonacci_calculator.csv")).unwrap();
+        assert_eq!(sectioned.network().get_reactions(), parsed.get_reactions());
+        // a row written twice is listed twice in its section
+        assert_eq!(sectioned.reactions_in_section("Setup RXN Network").into_iter().collect::<HashSet<&Reaction>>().len(), parsed.get_reactions().len());
+
+        // without the initial solution every species starts at zero
+        let network = sectioned.without_sections(&["Initialize solution"]);
+        assert_eq!(network.get_reactions(), parsed.get_reactions());
+        assert!(network.get_solution().species_counts.values().all(|count| *count == Count(0)));
+        assert!(sectioned.without_sections(&["Setup RXN Network"]).get_reactions().is_empty());
+    }
+}
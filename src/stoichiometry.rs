@@ -0,0 +1,180 @@
+//! Exports a reaction network as stoichiometry matrices for numerical analysis.
+//!
+//! Matrices have a row for every species and a column for every reaction. Species are ordered by name
+//! and reactions by their csv row, the same orderings used by [crate::writer::CSVwriter], so the same network always
+//! produces the same matrices. Every file is plain comma separated integers without a header,
+//! except the index which maps each row and column back to its species or reaction.
+//!
+//! | file | contents |
+//! |---|---|
+//! | `reactants.csv` | species × reactions, how many of each species a reaction consumes |
+//! | `products.csv` | species × reactions, how many of each species a reaction produces |
+//! | `stoichiometry.csv` | species × reactions, products minus reactants |
+//! | `rates.csv` | one reaction rate per line |
+//! | `initial_state.csv` | one initial species count per line |
+//! | `index.csv` | `kind,position,name` rows such as `species,0,A` or `reaction,0,A + 2 B => C` |
+//!
+//! ```python
+//! import numpy as np
+//! net = np.loadtxt("stoichiometry.csv", delimiter=",", dtype=np.int64, ndmin=2)
+//! rates = np.loadtxt("rates.csv", dtype=np.int64, ndmin=1)
+//! ```
+
+use std::{fs, io, path::Path};
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, reaction::{Reaction, term::Term}, solution::Name};
+
+use crate::writer::CSVwriter;
+
+/// A reaction network as matrices, rows are indexed by `species` and columns by `reactions`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoichiometryMatrices {
+    pub species: Vec<Name>,
+    /// each reaction as its csv row without the rate, ie `A + 2 B => C`
+    pub reactions: Vec<String>,
+    pub reactants: Vec<Vec<u64>>,
+    pub products: Vec<Vec<u64>>,
+    pub net: Vec<Vec<i128>>,
+    pub rates: Vec<u64>,
+    pub initial_state: Vec<u64>,
+}
+
+/// Serializes a [ReactionNetwork] into stoichiometry matrices
+pub struct StoichiometryWriter;
+
+impl StoichiometryWriter {
+    /// build the matrices of a network with species sorted by name and reactions by their csv row
+    pub fn as_matrices(reaction_network: &ReactionNetwork) -> StoichiometryMatrices {
+        let mut species = reaction_network.get_solution().species_counts.keys().cloned().collect::<Vec<Name>>();
+        species.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let mut reactions = reaction_network.get_reactions().iter().collect::<Vec<&Reaction>>();
+        reactions.sort_by_cached_key(|reaction| CSVwriter::as_row(reaction));
+
+        let reactants = Self::as_matrix(&species, &reactions, Reaction::get_reactants);
+        let products = Self::as_matrix(&species, &reactions, Reaction::get_products);
+        let net = reactants.iter().zip(&products)
+            .map(|(consumed, produced)| consumed.iter().zip(produced).map(|(&consumed, &produced)| i128::from(produced) - i128::from(consumed)).collect())
+            .collect();
+
+        let initial_state = species.iter()
+            .map(|name| reaction_network.get_solution().species_counts.get(name).map_or(0, |count| count.0))
+            .collect();
+
+        StoichiometryMatrices {
+            reactions: reactions.iter().map(|reaction| Self::as_label(reaction)).collect(),
+            rates: reactions.iter().map(|reaction| reaction.get_reaction_rate()).collect(),
+            species,
+            reactants,
+            products,
+            net,
+            initial_state,
+        }
+    }
+
+    /// every exported file as its file name and contents
+    pub fn as_files(reaction_network: &ReactionNetwork) -> Vec<(&'static str, String)> {
+        let matrices = Self::as_matrices(reaction_network);
+
+        let mut index = String::from("kind,position,name\n");
+        for (position, name) in matrices.species.iter().enumerate() {
+            index.push_str(&format!("species,{},{}\n", position, name.0));
+        }
+        for (position, label) in matrices.reactions.iter().enumerate() {
+            index.push_str(&format!("reaction,{},{}\n", position, label));
+        }
+
+        vec![
+            ("reactants.csv", Self::as_rows(&matrices.reactants)),
+            ("products.csv", Self::as_rows(&matrices.products)),
+            ("stoichiometry.csv", Self::as_rows(&matrices.net)),
+            ("rates.csv", Self::as_column(&matrices.rates)),
+            ("initial_state.csv", Self::as_column(&matrices.initial_state)),
+            ("index.csv", index),
+        ]
+    }
+
+    /// write every exported file into a directory, creating it if it does not exist
+    pub fn write(reaction_network: &ReactionNetwork, directory: &Path) -> io::Result<()> {
+        fs::create_dir_all(directory)?;
+        for (file_name, contents) in Self::as_files(reaction_network) {
+            fs::write(directory.join(file_name), contents)?;
+        }
+        Ok(())
+    }
+
+    /// coefficients of every species in every reaction for one side of the reactions
+    fn as_matrix(species: &[Name], reactions: &[&Reaction], side: fn(&Reaction) -> &Vec<Term>) -> Vec<Vec<u64>> {
+        species.iter()
+        .map(|name| reactions.iter()
+            .map(|reaction| side(reaction).iter()
+                .filter(|term| term.get_species_name() == name)
+                .map(|term| term.get_coefficient().0)
+                .sum())
+            .collect())
+        .collect()
+    }
+
+    /// a reaction row without its rate so it names the reaction in the index
+    fn as_label(reaction: &Reaction) -> String {
+        let row = CSVwriter::as_row(reaction);
+        let rate_start = row.trim_end_matches(',').rfind(',').unwrap_or(row.len());
+        row[..rate_start].to_string()
+    }
+
+    fn as_rows<T: ToString>(matrix: &[Vec<T>]) -> String {
+        matrix.iter()
+        .map(|row| row.iter().map(T::to_string).collect::<Vec<String>>().join(",") + "\n")
+        .collect()
+    }
+
+    fn as_column<T: ToString>(vector: &[T]) -> String {
+        vector.iter().map(|value| value.to_string() + "\n").collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use marlea_engine::trial::reaction_network::solution::Name;
+
+    use crate::{CSVparser, MarleaParser, stoichiometry::StoichiometryWriter};
+
+    #[test]
+    fn stoichiometry_writer_orders_species_and_reactions() {
+        let network = CSVparser::as_reaction_network("C => NULL,1,\nA + 2 B => C + A,10,\nNULL => A,3,\nA,5,\n").unwrap();
+
+        let files = StoichiometryWriter::as_files(&network);
+        let file = |name: &str| files.iter().find(|(file_name, _)| *file_name == name).unwrap().1.as_str();
+        // reactions are ordered A + 2 B => C + A, C => NULL, NULL => A, each keeping the order its terms were written in
+        assert_eq!(file("reactants.csv"), "1,0,0\n2,0,0\n0,1,0\n");
+        assert_eq!(file("products.csv"), "1,0,1\n0,0,0\n1,0,0\n");
+        assert_eq!(file("stoichiometry.csv"), "0,0,1\n-2,0,0\n1,-1,0\n");
+        assert_eq!(file("rates.csv"), "10\n1\n3\n");
+        assert_eq!(file("initial_state.csv"), "5\n0\n0\n");
+        assert_eq!(file("index.csv"), concat!(
+            "kind,position,name\n",
+            "species,0,A\n",
+            "species,1,B\n",
+            "species,2,C\n",
+            "reaction,0,A + 2 B => C + A\n",
+            "reaction,1,C => NULL\n",
+            "reaction,2,NULL => A\n",
+        ));
+    }
+
+    #[test]
+    fn stoichiometry_matrices_match_fibonacci_dimensions() {
+        let parsed = MarleaParser::parse(&Path::new("test_data").join("Fibonacci_calculator.csv")).unwrap();
+
+        let matrices = StoichiometryWriter::as_matrices(&parsed);
+        assert_eq!(matrices.species.len(), parsed.get_solution().species_counts.len());
+        assert_eq!(matrices.reactions.len(), parsed.get_reactions().len());
+        assert!(matrices.net.iter().all(|row| row.len() == matrices.reactions.len()));
+        assert_eq!(matrices.rates.len(), matrices.reactions.len());
+
+        let position = matrices.species.iter().position(|name| *name == Name(String::from("next_value"))).unwrap();
+        assert_eq!(matrices.initial_state[position], parsed.get_solution().species_counts[&matrices.species[position]].0);
+    }
+}
@@ -0,0 +1,275 @@
+//! A lossless syntax tree of csv network files.
+//!
+//! Interpreting a network throws away everything which does not change it, comments, blank rows and how rows were written.
+//! The syntax tree keeps all of it, every line of the source becomes a [SyntaxRow] holding its exact text,
+//! the [Statement] written on it with the original text and span of each part, and any `//` comment written after it.
+//! Joining the text of every row gives back the source unchanged, so editors, formatters and documentation generators
+//! can work from the same parse as [crate::MarleaParser].
+//!
+//! Rows which can not be parsed are kept as [StatementKind::Malformed] rather than failing the whole tree.
+//! A template spans several rows, its header row holds [StatementKind::Template], each row of its body follows as usual
+//! and the closing row holds [StatementKind::TemplateEnd].
+//!
+//! ```text
+//! ,//Setup RXN Network,                  row 1, no statement and the comment `//Setup RXN Network,`
+//! fibonacci.call => setup.call,1,        row 2, a reaction with one reactant and one product
+//! ,,                                     row 3, no statement and no comment
+//! ```
+
+use pest::{Parser, iterators::Pair};
+
+use crate::{CSVparser, MarleaParserError, Rule, SourceSpan};
+
+/// Every line of a csv network in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxTree {
+    pub rows: Vec<SyntaxRow>,
+}
+
+/// A single line of source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxRow {
+    /// the line exactly as written, including its line ending
+    pub text: String,
+    /// location of the line without its line ending
+    pub span: SourceSpan,
+    pub statement: Option<Statement>,
+    pub comment: Option<Comment>,
+}
+
+/// The reaction, species count or directive written on a row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    /// the statement as written, without the commas and comment which follow it
+    pub text: String,
+    pub span: SourceSpan,
+}
+
+/// What a statement declares, rates, counts and values are kept as the expression text written in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatementKind {
+    Reaction { reactants: Vec<SyntaxTerm>, products: Vec<SyntaxTerm>, rate: String },
+    ReversibleReaction { reactants: Vec<SyntaxTerm>, products: Vec<SyntaxTerm>, forward_rate: String, reverse_rate: String },
+    SpeciesCount { name: String, count: String },
+    Parameter { name: String, value: String },
+    Include { path: String },
+    Instance { template: String, arguments: Vec<String> },
+    Template { name: String, parameters: Vec<String> },
+    TemplateEnd,
+    /// a row which could not be parsed, see [crate::MarleaParser::parse_recovering] for a description of the problem
+    Malformed,
+}
+
+/// A species and its coefficient on one side of a reaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxTerm {
+    /// the coefficient as written, None when it is implicitly 1
+    pub coefficient: Option<String>,
+    pub name: String,
+    pub span: SourceSpan,
+}
+
+/// A `//` comment and where it was written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    /// the comment as written from its leading `//` to the end of the line, including any trailing commas
+    pub text: String,
+    pub span: SourceSpan,
+}
+
+impl Comment {
+    /// the words of the comment without the leading `//` or trailing commas, ie `Setup RXN Network`
+    pub fn content(&self) -> &str {
+        self.text.trim_start_matches('/').trim_end_matches([',', ' ', '\t']).trim_start()
+    }
+}
+
+impl SyntaxTree {
+    /// parse csv source text into a syntax tree, only failing if pest can not split the source into rows
+    pub fn parse(source: &str) -> Result<SyntaxTree, MarleaParserError> {
+        let recovering_network = match CSVparser::parse(Rule::recovering_network, source) {
+            Ok(mut token_stream) => match token_stream.next() {
+                Some(token) => token,
+                None => return Result::Err(MarleaParserError::EmptyTokenStream { path: None }),
+            },
+            Err(msg) => return Result::Err(MarleaParserError::from(msg)),
+        };
+
+        let mut rows = Vec::new();
+        let mut line_start = 0;
+        for text in source.split_inclusive('\n') {
+            let content = text.trim_end_matches(['\n', '\r']);
+            let mut span = SourceSpan::from_offset(source, line_start);
+            span.end = line_start + content.len();
+            rows.push(SyntaxRow { text: text.to_string(), span, statement: None, comment: None });
+            line_start += text.len();
+        }
+
+        for token in recovering_network.into_inner() {
+            Self::place(source, token, &mut rows);
+        }
+
+        Result::Ok(SyntaxTree { rows })
+    }
+
+    /// the source text the tree was parsed from
+    pub fn to_source(&self) -> String {
+        self.rows.iter().map(|row| row.text.as_str()).collect()
+    }
+
+    /// every statement in the tree in source order
+    pub fn statements(&self) -> impl Iterator<Item = &Statement> {
+        self.rows.iter().filter_map(|row| row.statement.as_ref())
+    }
+
+    /// attach a token to the row of the line it starts on
+    fn place(source: &str, token: Pair<'_, Rule>, rows: &mut [SyntaxRow]) {
+        let line = token.line_col().0 - 1;
+        match token.as_rule() {
+            Rule::EOI => (),
+            Rule::comment => rows[line].comment = Some(Comment { text: token.as_str().to_string(), span: SourceSpan::from_pair(&token) }),
+            Rule::template => {
+                let start = token.as_span().start();
+                let end = token.as_span().end();
+                let mut name = String::new();
+                let mut parameters = Vec::new();
+                let mut header_end = start;
+
+                for sub_token in token.into_inner() {
+                    match sub_token.as_rule() {
+                        Rule::parameter_name => name = sub_token.as_str().to_string(),
+                        Rule::template_parameters => {
+                            header_end = sub_token.as_span().end();
+                            parameters = sub_token.into_inner().map(|parameter| parameter.as_str().to_string()).collect();
+                        },
+                        _ => Self::place(source, sub_token, rows),
+                    }
+                }
+
+                rows[line].statement = Some(Self::statement(source, StatementKind::Template { name, parameters }, start, header_end));
+                let last_line = source[..end].matches('\n').count();
+                rows[last_line].statement = Some(Self::statement(source, StatementKind::TemplateEnd, end - "#end".len(), end));
+            },
+            _ => {
+                let span = SourceSpan::from_pair(&token);
+                let text = token.as_str().to_string();
+                rows[line].statement = Some(Statement { kind: Self::as_kind(token), text, span });
+            },
+        }
+    }
+
+    fn statement(source: &str, kind: StatementKind, start: usize, end: usize) -> Statement {
+        let mut span = SourceSpan::from_offset(source, start);
+        span.end = end;
+        Statement { kind, text: source[start..end].to_string(), span }
+    }
+
+    fn as_kind(token: Pair<'_, Rule>) -> StatementKind {
+        let rule = token.as_rule();
+        let mut inner = token.into_inner();
+        let mut text = || inner.next().map(|sub_token| sub_token.as_str().to_string()).unwrap_or_default();
+
+        match rule {
+            Rule::reaction => {
+                let (reactants, products) = (Self::as_terms(inner.next()), Self::as_terms(inner.next()));
+                let rate = inner.next().map(|rate| rate.as_str().to_string()).unwrap_or_default();
+                StatementKind::Reaction { reactants, products, rate }
+            },
+            Rule::reversible_reaction => {
+                let (reactants, products) = (Self::as_terms(inner.next()), Self::as_terms(inner.next()));
+                let mut rates = inner.map(|rate| rate.as_str().to_string());
+                let (forward_rate, reverse_rate) = (rates.next().unwrap_or_default(), rates.next().unwrap_or_default());
+                StatementKind::ReversibleReaction { reactants, products, forward_rate, reverse_rate }
+            },
+            Rule::species_count => StatementKind::SpeciesCount { name: text(), count: text() },
+            Rule::parameter => StatementKind::Parameter { name: text(), value: text() },
+            Rule::include => StatementKind::Include { path: text() },
+            Rule::instance => {
+                let template = text();
+                StatementKind::Instance { template, arguments: inner.map(|argument| argument.as_str().to_string()).collect() }
+            },
+            _ => StatementKind::Malformed,
+        }
+    }
+
+    /// the terms of a reactants or products token, NULL has none
+    fn as_terms(side: Option<Pair<'_, Rule>>) -> Vec<SyntaxTerm> {
+        side.into_iter()
+        .flat_map(|side| side.into_inner())
+        .map(|term| {
+            let span = SourceSpan::from_pair(&term);
+            let mut coefficient = None;
+            let mut name = String::new();
+            for sub_token in term.into_inner() {
+                match sub_token.as_rule() {
+                    Rule::coefficient => coefficient = Some(sub_token.as_str().to_string()),
+                    _ => name = sub_token.as_str().to_string(),
+                }
+            }
+            SyntaxTerm { coefficient, name, span }
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{MarleaParser, syntax::{StatementKind, SyntaxTree}};
+
+    #[test]
+    fn syntax_tree_is_lossless() {
+        let source = MarleaParser::read_source(&Path::new("test_data").join("Fibonacci_calculator.csv")).unwrap();
+
+        let tree = SyntaxTree::parse(&source).unwrap();
+        assert_eq!(tree.to_source(), source);
+        assert_eq!(tree.rows.len(), source.lines().count());
+
+        let header = &tree.rows[3];
+        assert!(header.statement.is_none());
+        assert_eq!(header.comment.as_ref().unwrap().content(), "Setup RXN Network");
+        assert_eq!(header.comment.as_ref().unwrap().span.line, 4);
+    }
+
+    #[test]
+    fn syntax_tree_keeps_statement_parts() {
+        let source = concat!(
+            "#param fast = 10,// rate of cleanup,\n",
+            "destruct + 2 next_value => next_value , fast * 10 ,\n",
+            "\n",
+            "#template not(X)\n",
+            "X <=> NULL,1,fast,\n",
+            "#end\n",
+            "A => => B,1,\n",
+            "A,5,\n",
+        );
+        let tree = SyntaxTree::parse(source).unwrap();
+
+        let parameter = tree.rows[0].statement.as_ref().unwrap();
+        assert_eq!(parameter.kind, StatementKind::Parameter { name: String::from("fast"), value: String::from("10") });
+        assert_eq!(tree.rows[0].comment.as_ref().unwrap().content(), "rate of cleanup");
+
+        let reaction = tree.rows[1].statement.as_ref().unwrap();
+        assert_eq!(reaction.text, "destruct + 2 next_value => next_value , fast * 10");
+        match &reaction.kind {
+            StatementKind::Reaction { reactants, products, rate } => {
+                assert_eq!(reactants[1].coefficient.as_deref(), Some("2"));
+                assert_eq!(reactants[1].name, "next_value");
+                assert_eq!((reactants[1].span.line, reactants[1].span.column), (2, 12));
+                assert_eq!(products.len(), 1);
+                assert_eq!(rate, "fast * 10");
+            },
+            other => panic!("expected a reaction, found {:?}", other),
+        }
+
+        assert!(tree.rows[2].statement.is_none() && tree.rows[2].comment.is_none());
+        assert_eq!(tree.rows[3].statement.as_ref().unwrap().kind, StatementKind::Template { name: String::from("not"), parameters: vec![String::from("X")] });
+        assert!(matches!(tree.rows[4].statement.as_ref().unwrap().kind, StatementKind::ReversibleReaction { ref reverse_rate, .. } if reverse_rate == "fast"));
+        assert_eq!(tree.rows[5].statement.as_ref().unwrap().kind, StatementKind::TemplateEnd);
+        assert_eq!(tree.rows[6].statement.as_ref().unwrap().kind, StatementKind::Malformed);
+        assert_eq!(tree.statements().count(), 7);
+        assert_eq!(tree.to_source(), source);
+    }
+}
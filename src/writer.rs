@@ -0,0 +1,111 @@
+//! Writes a reaction network back out as csv which [crate::MarleaParser] can read back in.
+//!
+//! Output is canonical so the same network always produces the same text,
+//! species counts come first sorted by name followed by every reaction sorted by its row.
+//!
+//! ```text
+//! ,//species counts,
+//! A,5,
+//! B,0,
+//! C,0,
+//! ,//reactions,
+//! A + 2 B => C,10,
+//! C => NULL,1,
+//! ```
+
+use std::io::{self, Write};
+
+use marlea_engine::trial::reaction_network::{ReactionNetwork, reaction::{Reaction, term::Term}};
+
+/// Serializes a [ReactionNetwork] into the csv format
+pub struct CSVwriter;
+
+impl CSVwriter {
+    /// write the network as canonical csv text
+    pub fn as_csv(reaction_network: &ReactionNetwork) -> String {
+        let mut species_counts = reaction_network.get_solution().species_counts.iter()
+            .map(|(name, count)| (name.0.as_str(), count.0))
+            .collect::<Vec<(&str, u64)>>();
+        species_counts.sort_unstable();
+
+        let mut reactions = reaction_network.get_reactions().iter()
+            .map(Self::as_row)
+            .collect::<Vec<String>>();
+        reactions.sort_unstable();
+
+        let mut output = String::from(",//species counts,\n");
+        for (name, count) in species_counts {
+            output.push_str(&format!("{},{},\n", name, count));
+        }
+        output.push_str(",//reactions,\n");
+        for reaction in reactions {
+            output.push_str(&reaction);
+            output.push('\n');
+        }
+
+        output
+    }
+
+    /// write the network as canonical csv text to any writer such as a file
+    pub fn write(reaction_network: &ReactionNetwork, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(Self::as_csv(reaction_network).as_bytes())
+    }
+
+    /// a single reaction row ie `A + 2 B => C,10,`
+    pub(crate) fn as_row(reaction: &Reaction) -> String {
+        format!("{} => {},{},", Self::as_side(reaction.get_reactants()), Self::as_side(reaction.get_products()), reaction.get_reaction_rate())
+    }
+
+    /// terms separated by plus signs, or NULL for a side with no terms
+    fn as_side(terms: &[Term]) -> String {
+        if terms.is_empty() {
+            return String::from("NULL");
+        }
+
+        terms.iter()
+        .map(|term| match term.get_coefficient().0 {
+            1 => term.get_species_name().0.clone(),
+            coefficient => format!("{} {}", coefficient, term.get_species_name().0),
+        })
+        .collect::<Vec<String>>()
+        .join(" + ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{CSVparser, MarleaParser, writer::CSVwriter};
+
+    #[test]
+    fn csv_writer_round_trips_fibonacci() {
+        let parsed = MarleaParser::parse(&Path::new("test_data").join("Fibonacci_calculator.csv")).unwrap();
+
+        let written = CSVwriter::as_csv(&parsed);
+        let reparsed = CSVparser::as_reaction_network(&written).unwrap();
+        assert_eq!(parsed.get_reactions(), reparsed.get_reactions());
+        assert_eq!(parsed.get_solution().species_counts, reparsed.get_solution().species_counts);
+
+        // writing is canonical so a second round trip produces identical text
+        assert_eq!(CSVwriter::as_csv(&reparsed), written);
+    }
+
+    #[test]
+    fn csv_writer_writes_canonical_rows() {
+        let network = CSVparser::as_reaction_network("C => NULL,1,\nA + 2 B => C,2.5e1,\nNULL => A,1,\nA,5,\n").unwrap();
+
+        let mut written = Vec::new();
+        CSVwriter::write(&network, &mut written).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), concat!(
+            ",//species counts,\n",
+            "A,5,\n",
+            "B,0,\n",
+            "C,0,\n",
+            ",//reactions,\n",
+            "A + 2 B => C,25,\n",
+            "C => NULL,1,\n",
+            "NULL => A,1,\n",
+        ));
+    }
+}